@@ -0,0 +1,45 @@
+#![feature(test)]
+#![feature(ptr_metadata)]
+extern crate test;
+
+use dst_init::{dst, BoxExt, Slice, SliceExt};
+use test::{black_box, Bencher};
+
+const LEN: usize = 4 << 20;
+
+#[dst]
+struct Packet {
+    id: u32,
+    data: [u8],
+}
+
+fn bytes() -> Vec<u8> {
+    (0..LEN).map(|i| i as u8).collect()
+}
+
+#[bench]
+fn iter_init(b: &mut Bencher) {
+    let src = bytes();
+    b.iter(|| Box::<Packet>::emplace(Packet::init(1, Slice::iter_init(src.len(), black_box(&src).iter().copied()))));
+}
+
+#[bench]
+fn fn_init(b: &mut Bencher) {
+    let src = bytes();
+    b.iter(|| {
+        let mut iter = black_box(&src).iter();
+        Box::<Packet>::emplace(Packet::init(1, Slice::fn_init(src.len(), || *iter.next().unwrap())))
+    });
+}
+
+#[bench]
+fn copy_from(b: &mut Bencher) {
+    let src = bytes();
+    b.iter(|| Box::<Packet>::emplace(Packet::init(1, Slice::copy_from(black_box(&src)))));
+}
+
+#[bench]
+fn clone_from(b: &mut Bencher) {
+    let src = bytes();
+    b.iter(|| Box::<Packet>::emplace(Packet::init(1, Slice::clone_from(black_box(&src)))));
+}
@@ -1,17 +1,179 @@
 #![feature(proc_macro_diagnostic)]
 #![feature(const_trait_impl)]
 #![feature(decl_macro)]
+#![allow(unused_features)]
 
 use proc_macro::{TokenStream};
 use quote::{format_ident, ToTokens};
-use syn::{GenericParam, ItemStruct, GenericArgument, Member, Index, ItemImpl};
-use quote::{quote};
+use syn::{GenericParam, ItemStruct, GenericArgument, Member, Index, ItemImpl, Item, Fields, Type, Meta, NestedMeta, Ident, Visibility, Lit, Expr, FieldValue};
+use quote::{quote, quote_spanned};
+use proc_macro2::Span;
+use syn::parse::{ParseStream, Parser};
 use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
 use syn::token::{Comma};
 
+/// How far a visibility reaches, `None` for `pub(in path)` which is not compared.
+fn vis_rank(vis: &Visibility) -> Option<u8> {
+    match vis {
+        Visibility::Public(_) => Some(3),
+        Visibility::Crate(_) => Some(2),
+        Visibility::Restricted(r) if r.in_token.is_none() && r.path.is_ident("crate") => Some(2),
+        Visibility::Restricted(r) if r.in_token.is_none() && r.path.is_ident("super") => Some(1),
+        Visibility::Restricted(r) if r.in_token.is_none() && r.path.is_ident("self") => Some(0),
+        Visibility::Inherited => Some(0),
+        _ => None,
+    }
+}
+
+/// Whether an item of visibility `vis` is visible everywhere an item of visibility `other` is.
+fn reaches(vis: &Visibility, other: &Visibility) -> bool {
+    vis.to_token_stream().to_string() == other.to_token_stream().to_string()
+        || vis_rank(other) == Some(0)
+        || matches!((vis_rank(vis), vis_rank(other)), (Some(a), Some(b)) if a >= b)
+}
+
+/// Arguments of `#[dst(...)]`.
+#[derive(Default)]
+struct Options {
+    init: Option<Ident>,
+    fst: Option<Ident>,
+    init_vis: Option<Visibility>,
+    fst_vis: Option<Visibility>,
+    no_fst: bool,
+}
+
+/// Parse the arguments of `#[dst(...)]`, emitting errors for the invalid ones.
+fn parse_options(attr: TokenStream) -> Option<Options> {
+    let args = match Punctuated::<NestedMeta, Comma>::parse_terminated.parse(attr) {
+        Ok(args) => args,
+        Err(e) => {
+            e.span().unwrap().error(e.to_string()).emit();
+            return None;
+        }
+    };
+    let mut options = Options::default();
+    let mut valid = true;
+    for arg in args.iter() {
+        match arg {
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("no_fst") => options.no_fst = true,
+            NestedMeta::Meta(Meta::NameValue(nv)) if ["init", "fst", "init_vis", "fst_vis"].iter().any(|i| nv.path.is_ident(i)) => {
+                let Lit::Str(lit) = &nv.lit else {
+                    valid = false;
+                    nv.lit.span().unwrap()
+                        .error("expected a string literal")
+                        .emit();
+                    continue;
+                };
+                let key = nv.path.get_ident().unwrap().to_string();
+                let parsed = match key.as_str() {
+                    "init" => lit.parse().map(|i| options.init = Some(i)),
+                    "fst" => lit.parse().map(|i| options.fst = Some(i)),
+                    "init_vis" => lit.parse().map(|v| options.init_vis = Some(v)),
+                    _ => lit.parse().map(|v| options.fst_vis = Some(v)),
+                };
+                if parsed.is_err() {
+                    valid = false;
+                    let expected = if key.ends_with("vis") { "a visibility" } else { "an identifier" };
+                    lit.span().unwrap()
+                        .error(format!("expected {} for `{}`", expected, key))
+                        .emit();
+                }
+            }
+            arg => {
+                valid = false;
+                arg.span().unwrap()
+                    .error("unsupported `#[dst]` argument")
+                    .help("supported arguments are `init = \"..\"`, `fst = \"..\"`, `init_vis = \"..\"`, `fst_vis = \"..\"` and `no_fst`")
+                    .emit();
+            }
+        }
+    }
+    if options.no_fst && (options.fst.is_some() || options.fst_vis.is_some()) {
+        valid = false;
+        args.span().unwrap()
+            .error("`no_fst` can not be used with `fst` or `fst_vis`")
+            .emit();
+    }
+    valid.then_some(options)
+}
+
+/// Whether the type is sized for sure, judging by its syntax only.
+fn is_sized(ty: &Type) -> bool {
+    const SIZED: &[&str] = &[
+        "bool", "char", "f32", "f64", "i8", "i16", "i32", "i64", "i128", "isize",
+        "u8", "u16", "u32", "u64", "u128", "usize", "String", "Vec", "Box", "Option",
+    ];
+    match ty {
+        Type::Array(_) | Type::Tuple(_) | Type::Reference(_) | Type::Ptr(_)
+        | Type::BareFn(_) | Type::Never(_) | Type::ImplTrait(_) => true,
+        Type::Paren(t) => is_sized(&t.elem),
+        Type::Group(t) => is_sized(&t.elem),
+        Type::Path(t) => t.qself.is_none()
+            && t.path.segments.len() == 1
+            && SIZED.iter().any(|i| t.path.segments[0].ident == i),
+        _ => false,
+    }
+}
+
+/// Emit errors for structs `#[dst]` can not handle. Returns whether the struct is valid and
+/// whether it already has `#[repr(C)]`.
+fn check_struct(item: &ItemStruct) -> (bool, bool) {
+    let mut valid = true;
+    let mut repr_c = false;
+    for attr in item.attrs.iter().filter(|a| a.path.is_ident("repr")) {
+        let Ok(Meta::List(list)) = attr.parse_meta() else {
+            continue;
+        };
+        for nested in list.nested.iter() {
+            let NestedMeta::Meta(meta) = nested else {
+                continue;
+            };
+            let path = meta.path();
+            if path.is_ident("C") {
+                repr_c = true;
+            } else if path.is_ident("packed") {
+                valid = false;
+                nested.span().unwrap()
+                    .error("`#[dst]` does not support `repr(packed)`")
+                    .note("the tail of the struct is placed at its own alignment")
+                    .help("remove `packed`, or wrap the packed fields in a sized struct")
+                    .emit();
+            } else if !path.is_ident("align") {
+                valid = false;
+                nested.span().unwrap()
+                    .error(format!("`repr({})` conflicts with the `repr(C)` added by `#[dst]`", path.to_token_stream()))
+                    .emit();
+            }
+        }
+    }
+    let Some(last) = item.fields.iter().next_back() else {
+        valid = false;
+        let span = match &item.fields {
+            Fields::Unit => item.ident.span(),
+            fields => fields.span(),
+        };
+        span.unwrap()
+            .error("`#[dst]` struct has no field")
+            .help("add an unsized field, like `[T]`, `str` or `dyn Trait`, as the last field")
+            .emit();
+        return (valid, repr_c);
+    };
+    if is_sized(&last.ty) {
+        valid = false;
+        last.ty.span().unwrap()
+            .error("the last field of a `#[dst]` struct must be unsized")
+            .help("use a slice `[T]`, `str`, `dyn Trait` or another `#[dst]` struct")
+            .emit();
+    }
+    (valid, repr_c)
+}
+
 /// # Usage:
 /// Add `#[dst]` ahead of struct item as below:
 /// ```rust
+/// # #![feature(ptr_metadata)]
+/// # use dst_init::dst;
 /// #[dst]
 /// struct Foo{
 ///     a:u8,
@@ -20,6 +182,7 @@ use syn::token::{Comma};
 /// ```
 /// after expansion:
 /// ```rust
+/// # use dst_init::EmplaceInitializer;
 /// use std::marker::PhantomData;
 /// #[repr(C)]
 /// struct Foo{
@@ -38,18 +201,38 @@ use syn::token::{Comma};
 ///     a:u8,
 ///     b:PhantomData<[usize]>,
 /// }
+///
+/// struct FooFstMut<'a>{
+///     a:&'a mut u8,
+///     b:PhantomData<&'a mut [usize]>,
+/// }
 /// ```
+/// `FooInit<INIT>` implements `EmplaceInitializer<Output=Foo>`, and also
+/// `TryEmplaceInitializer` when `INIT` implements `TryEmplaceInitializer`.
+///
 /// You can also use it in nestly. With above Foo:
 /// ```rust
+/// # #![feature(ptr_metadata)]
+/// # use dst_init::dst;
+/// # #[dst]
+/// # struct Foo{
+/// #     a:u8,
+/// #     b:[usize],
+/// # }
 /// #[dst]
 /// struct Bar{
 ///     c:usize,
 ///     d:Foo
 /// }
 /// ```
-/// For bar there will be 3 structs `Bar`,`BarInit`,`BarFst` after expansion.
+/// For bar there will be 4 structs `Bar`,`BarInit`,`BarFst`,`BarFstMut` after expansion.
 /// The `BarInit` looks like this:
 /// ```rust
+/// # use dst_init::EmplaceInitializer;
+/// # struct Foo{
+/// #     a:u8,
+/// #     b:[usize],
+/// # }
 /// #[repr(C)]
 /// struct BarInit<INIT:EmplaceInitializer<Output=Foo>>{
 ///     c:usize,
@@ -57,38 +240,97 @@ use syn::token::{Comma};
 /// }
 /// ```
 ///
-/// # Use Case:
-/// - 1 add simpler api
+/// The generated types can be configured by arguments:
+/// - `init = "FooBuilder"`, `fst = "FooHeader"`: names of the generated types.
+/// - `init_vis = "pub(crate)"`, `fst_vis = "pub"`: visibility of the generated types, same as the
+///   original struct by default. Their fields keep the visibility of the original fields.
+///   `Initializer` is not implemented for the struct when the `Init` type is less visible.
+/// - `no_fst`: do not generate the `Fst` type.
+/// ```rust
+/// # #![feature(ptr_metadata)]
+/// # use dst_init::dst;
+/// #[dst(init = "FooBuilder", init_vis = "pub(crate)", no_fst)]
+/// pub struct Foo{
+///     pub a:u8,
+///     pub b:[usize],
+/// }
+/// ```
+///
+/// Tuple structs work the same way, the last field is the tail:
+/// ```rust
+/// # #![feature(ptr_metadata)]
+/// # use dst_init::dst;
+/// #[dst]
+/// struct Packet(u32, [u8]);
+/// // generates `PacketInit<INIT>(u32, INIT)` and `PacketFst(u32, PhantomData<[u8]>)`
+/// ```
+///
+/// `DstClone` is implemented for `Foo` when the fields before the tail are `Clone` and the tail
+/// is `DstClone`, so `Box<Foo>` can be cloned by `clone_box()`.
+///
+/// `Foo::into_parts(self: Box<Self>)` takes a boxed value apart into `(FooFst, Vec<T>)` when the
+/// tail is a slice `[T]`, or `(FooFst, Box<Tail>)` otherwise. It is not generated with `no_fst`.
+///
+/// `split()` borrows the header as `&FooFst` and the tail separately. `split_mut()` borrows the
+/// tail and the header fields mutably, as a `FooFstMut<'_>` holding a `&mut` to each of them: a
+/// `&mut FooFst` is not possible since the trailing padding of `FooFst` may overlap the tail, like
+/// with `{a: u64, b: u8, c: [u8]}`. They are not generated with `no_fst`.
+///
+/// When the tail is a slice, `dst_init::tail::SliceTail` is implemented, so the tail of a
+/// `Box<Foo>` can be resized by `dst_init::tail::DstBoxExt`.
 ///
-///   we usually provide a function to create the initializer
+/// # Constructors:
+/// `#[dst]` also generates constructors taking the fields in order, the tail being the last:
 /// ```rust
+/// # #![feature(ptr_metadata)]
+/// # use dst_init::{dst, BoxExt, Slice, SliceExt};
 /// #[dst]
 /// struct SomePacket{
 ///     src:u32,
 ///     dst:u32,
 ///     options:[u8],
 /// }
-/// impl SomePacket{
-///     fn initializer<Init:EmplaceInitializer<Output=[u8]>>(src:u32,dst:u32,init:Init)->SomePacketInit<Init>{
-///         SomePacketInit{
-///             src,
-///             dst,
-///             options:init
-///         }
-///     }
-/// }
-/// ```
 ///
+/// let init = SomePacket::init(1, 2, Slice::iter_init(3, 0..));
+/// let packet: Box<SomePacket> = Box::emplace(init);
+/// assert_eq!(packet.options, [0, 1, 2]);
+/// ```
+/// When the tail is a slice `[T]`, there are also `boxed_from_slice(src, dst, &[T])` (needs
+/// `T: Clone`) and `boxed_from_iter(src, dst, tail_len, iter)` emplacing in a `Box`, and the
+/// `rc_*`, `arc_*` versions of them.
 #[proc_macro_attribute]
-pub fn dst(_attr:TokenStream, input:TokenStream) -> TokenStream{
-    let item_struct:ItemStruct = syn::parse(input.clone()).unwrap();
+pub fn dst(attr:TokenStream, input:TokenStream) -> TokenStream{
+    let options = parse_options(attr);
+    let item_struct:ItemStruct = match syn::parse(input.clone()) {
+        Ok(Item::Struct(item)) => item,
+        Ok(item) => {
+            let span = match &item {
+                Item::Enum(item) => item.enum_token.span(),
+                Item::Union(item) => item.union_token.span(),
+                item => item.span(),
+            };
+            span.unwrap()
+                .error("`#[dst]` can only be applied to structs")
+                .emit();
+            return input;
+        }
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let (valid, repr_c) = check_struct(&item_struct);
+    let Some(options) = options.filter(|_| valid) else {
+        return input;
+    };
     let struct_name = item_struct.ident.clone();
     let mut struct_generics_param = item_struct.generics.params.clone();
     let struct_where_clause = item_struct.generics.where_clause.clone();
-    let dst_type = item_struct.fields.iter().last().unwrap().ty.clone();
+    let dst_type = match item_struct.fields.iter().next_back().unwrap().ty.clone() {
+        // `dyn A + 'a` needs parentheses after `&` or `Output =` in the generated code
+        Type::TraitObject(ty) if ty.bounds.len() > 1 => syn::parse(quote!((#ty)).into()).unwrap(),
+        ty => ty,
+    };
     let field_num = item_struct.fields.iter().len();
-    let dst_field:Member = item_struct.fields.iter().last().unwrap().ident
-        .clone().map_or(Member::Unnamed(Index::from(field_num)),|i|{
+    let dst_field:Member = item_struct.fields.iter().next_back().unwrap().ident
+        .clone().map_or(Member::Unnamed(Index::from(field_num - 1)),|i|{
         Member::Named(i)
     });
 
@@ -115,67 +357,318 @@ pub fn dst(_attr:TokenStream, input:TokenStream) -> TokenStream{
             last
         });
 
-    let mut new_struct = quote!(#[repr(C)]);
+    let mut new_struct = if repr_c { quote!() } else { quote!(#[repr(C)]) };
     new_struct.extend(item_struct.into_token_stream());
     let new_struct:ItemStruct = syn::parse(new_struct.into()).unwrap();
 
     let mut fst_struct = new_struct.clone();
-    let fst_ident = format_ident!("{}Fst",struct_name);
+    let fst_ident = options.fst.unwrap_or_else(|| format_ident!("{}Fst",struct_name));
     fst_struct.ident = fst_ident.clone();
+    if let Some(vis) = options.fst_vis {
+        fst_struct.vis = vis;
+    }
     fst_struct.fields.iter_mut()
-        .last().unwrap().ty = syn::parse(quote!(core::marker::PhantomData< #dst_type >).into()).unwrap();
+        .next_back().unwrap().ty = syn::parse(quote!(core::marker::PhantomData< #dst_type >).into()).unwrap();
+
+    // mutable borrows of the header fields, a `&mut FooFst` would cover its trailing padding, which
+    // may overlap the tail
+    let mut fst_mut_struct = fst_struct.clone();
+    let fst_mut_ident = format_ident!("{}Mut",fst_ident);
+    fst_mut_struct.ident = fst_mut_ident.clone();
+    let fst_mut_doc = format!("Mutable borrows of the fields of `{}`, returned by `{}::split_mut()`.", fst_ident, struct_name);
+    fst_mut_struct.attrs.clear();
+    fst_mut_struct.generics.params.insert(0, syn::parse(quote!('__dst).into()).unwrap());
+    for field in fst_mut_struct.fields.iter_mut() {
+        let ty = &field.ty;
+        field.ty = syn::parse(quote!(&'__dst mut #ty).into()).unwrap();
+    }
+    fst_mut_struct.fields.iter_mut()
+        .next_back().unwrap().ty = syn::parse(quote!(core::marker::PhantomData<&'__dst mut #dst_type>).into()).unwrap();
 
     let mut init_struct = new_struct.clone();
-    let init_ident = format_ident!("{}Init",struct_name.to_string());
+    let init_ident = options.init.unwrap_or_else(|| format_ident!("{}Init",struct_name));
     init_struct.ident = init_ident.clone();
+    if let Some(vis) = options.init_vis {
+        init_struct.vis = vis;
+    }
+    // the `Initializer` impl can not expose an `Init` type less visible than the struct
+    let impl_initializer = reaches(&init_struct.vis, &new_struct.vis);
     init_struct.generics.params
         .push(GenericParam::Type(syn::parse(quote!(INIT:dst_init::EmplaceInitializer<Output=#dst_type>).into()).unwrap()));
     init_struct.fields.iter_mut()
-        .last().unwrap().ty = syn::parse(quote!(INIT).into()).unwrap();
+        .next_back().unwrap().ty = syn::parse(quote!(INIT).into()).unwrap();
 
     let init_ident = init_struct.ident.clone();
+    // drops the written header if emplacing the tail unwinds or fails
+    let fst_guard = quote!(
+        struct FstGuard<T>(*mut T);
+        impl<T> Drop for FstGuard<T> {
+            #[inline(always)]
+            fn drop(&mut self) {
+                unsafe { core::ptr::drop_in_place(self.0) }
+            }
+        }
+    );
+    // layout of the fields before the tail, without the trailing padding of the Fst struct, the
+    // tail starts right after them
+    let fst_local = if options.no_fst { quote!(#[allow(dead_code)] #fst_struct) } else { quote!() };
+    let fst_prefix = quote!(
+        #fst_local
+        let fst_prefix = core::alloc::Layout::from_size_align(
+            core::mem::offset_of!(#fst_ident<#struct_generics_arg>, #dst_field),
+            core::mem::align_of::<#fst_ident<#struct_generics_arg>>(),
+        ).unwrap();
+    );
     let impl_emplace:ItemImpl = syn::parse(quote!(
         impl<#struct_generics_param INIT:dst_init::EmplaceInitializer<Output=#dst_type>> dst_init::EmplaceInitializer for #init_ident<#struct_generics_arg INIT>
             #struct_where_clause
         {
             type Output = #struct_name<#struct_generics_arg>;
             #[inline(always)]
-            fn layout(&mut self) -> core::alloc::Layout{
-                use core::alloc::Layout;
-                let layout = Layout::new::<#fst_ident<#struct_generics_arg>>();
-                layout
-                    .extend(self.#dst_field.layout())
-                    .unwrap()
+            fn layout(&mut self) -> core::result::Result<core::alloc::Layout, core::alloc::LayoutError>{
+                #fst_prefix
+                core::result::Result::Ok(fst_prefix
+                    .extend(self.#dst_field.layout()?)?
                     .0
-                    .pad_to_align()
+                    .pad_to_align())
             }
 
             #[inline(always)]
             fn emplace(mut self, ptr: core::ptr::NonNull<u8>) -> core::ptr::NonNull<Self::Output>{unsafe{
                 use core::ptr;
                 use core::ptr::NonNull;
-                use core::alloc::Layout;
                 use core::mem;
                 use dst_init::EmplaceInitializer;
 
-                let fst_layout = Layout::new::<#fst_ident<#struct_generics_arg>>();
-                let dst_layout = self.#dst_field.layout();
-                let dst = ptr
-                    .as_ptr()
-                    .add(mem::size_of::<#fst_ident<#struct_generics_arg>>())
-                    .add(fst_layout.padding_needed_for(dst_layout.align()));
+                #fst_prefix
+                let dst_layout = self.#dst_field.layout().unwrap();
+                let (_, offset) = fst_prefix.extend(dst_layout).unwrap();
+                let dst = ptr.as_ptr().add(offset);
                 let fst = ptr::read(&self as *const Self as *const _);
                 let dst_init = ptr::read(&self.#dst_field as *const INIT);
                 mem::forget(self);
-                ptr.as_ptr().cast::<#fst_ident<#struct_generics_arg>>().write(fst);
+                let fst_ptr = ptr.as_ptr().cast::<#fst_ident<#struct_generics_arg>>();
+                fst_ptr.write(fst);
+                #fst_guard
+                let guard = FstGuard(fst_ptr);
                 let (_, meta) = dst_init
                     .emplace(NonNull::new(dst.cast()).unwrap())
                     .to_raw_parts();
+                mem::forget(guard);
                 mem::transmute(NonNull::<#dst_type>::from_raw_parts(ptr, meta))
             }}
         }
     ).into()).unwrap();
 
+    let impl_try_emplace:ItemImpl = syn::parse(quote!(
+        impl<#struct_generics_param INIT:dst_init::TryEmplaceInitializer<Output=#dst_type>> dst_init::TryEmplaceInitializer for #init_ident<#struct_generics_arg INIT>
+            #struct_where_clause
+        {
+            type Error = <INIT as dst_init::TryEmplaceInitializer>::Error;
+
+            #[inline(always)]
+            fn try_emplace(mut self, ptr: core::ptr::NonNull<u8>) -> core::result::Result<core::ptr::NonNull<Self::Output>, Self::Error>{unsafe{
+                use core::ptr;
+                use core::ptr::NonNull;
+                use core::mem;
+
+                #fst_prefix
+                let dst_layout = dst_init::EmplaceInitializer::layout(&mut self.#dst_field).unwrap();
+                let (_, offset) = fst_prefix.extend(dst_layout).unwrap();
+                let dst = ptr.as_ptr().add(offset);
+                let fst = ptr::read(&self as *const Self as *const _);
+                let dst_init = ptr::read(&self.#dst_field as *const INIT);
+                mem::forget(self);
+                let fst_ptr = ptr.as_ptr().cast::<#fst_ident<#struct_generics_arg>>();
+                fst_ptr.write(fst);
+                #fst_guard
+                let guard = FstGuard(fst_ptr);
+                let (_, meta) = dst_init::TryEmplaceInitializer::try_emplace(dst_init, NonNull::new(dst.cast()).unwrap())?
+                    .to_raw_parts();
+                mem::forget(guard);
+                core::result::Result::Ok(mem::transmute(NonNull::<#dst_type>::from_raw_parts(ptr, meta)))
+            }}
+        }
+    ).into()).unwrap();
+
+    // constructors taking the fields in order, the tail is the last parameter
+    let struct_vis = new_struct.vis.clone();
+    let init_vis = init_struct.vis.clone();
+    let names:Vec<Ident> = new_struct.fields.iter().enumerate()
+        .map(|(i,f)| f.ident.clone().unwrap_or_else(|| format_ident!("f{}",i)))
+        .collect();
+    let (tail_name, names) = names.split_last().unwrap();
+    let types:Vec<Type> = new_struct.fields.iter().map(|f| f.ty.clone()).collect();
+    let types = &types[..types.len() - 1];
+    let construct = |tail:proc_macro2::TokenStream| match &new_struct.fields {
+        Fields::Named(_) => quote!(#init_ident { #(#names,)* #tail_name: #tail }),
+        _ => quote!(#init_ident(#(#names,)* #tail)),
+    };
+    let init_construct = construct(quote!(#tail_name));
+    let mut constructors = quote!(
+        /// Create the initializer from the fields, with the initializer of the tail.
+        #[inline(always)]
+        #init_vis fn init<INIT:dst_init::EmplaceInitializer<Output=#dst_type>>(#(#names: #types,)* #tail_name: INIT) -> #init_ident<#struct_generics_arg INIT>{
+            #init_construct
+        }
+    );
+    if let Type::Slice(slice) = &dst_type {
+        let elem = &slice.elem;
+        // mixed-site so that it never clashes with a field named like it
+        let tail_len = Ident::new("tail_len", Span::mixed_site());
+        let from_slice = construct(quote!(
+            <dst_init::Slice<#elem> as dst_init::SliceExt>::clone_from(#tail_name)
+        ));
+        let from_iter = construct(quote!(
+            <dst_init::Slice<#elem> as dst_init::SliceExt>::iter_init(#tail_len, #tail_name.into_iter())
+        ));
+        for (prefix, name, ptr) in [
+            ("boxed", "Box", quote!(std::boxed::Box)),
+            ("rc", "Rc", quote!(std::rc::Rc)),
+            ("arc", "Arc", quote!(std::sync::Arc)),
+        ] {
+            let slice_fn = format_ident!("{}_from_slice",prefix);
+            let iter_fn = format_ident!("{}_from_iter",prefix);
+            let slice_doc = format!("Emplace in a `{}` with the tail cloned from a slice.", name);
+            let iter_doc = format!("Emplace in a `{}` with the tail taken from an iterator.", name);
+            let panic_doc = format!("would panic if the iterator has less items than `{}`.", tail_len);
+            constructors.extend(quote!(
+                #[doc = #slice_doc]
+                #[inline(always)]
+                #struct_vis fn #slice_fn(#(#names: #types,)* #tail_name: &[#elem]) -> #ptr<Self>
+                    where for<'__dst> #elem: core::clone::Clone
+                {
+                    <#ptr<Self> as dst_init::BoxExt>::emplace(#from_slice)
+                }
+
+                #[doc = #iter_doc]
+                ///
+                /// # Panics
+                #[doc = #panic_doc]
+                #[inline(always)]
+                #struct_vis fn #iter_fn<ITER:core::iter::IntoIterator<Item=#elem>>(#(#names: #types,)* #tail_len: usize, #tail_name: ITER) -> #ptr<Self>{
+                    <#ptr<Self> as dst_init::BoxExt>::emplace(#from_iter)
+                }
+            ));
+        }
+    }
+    // moves the header out through the Fst layout, then the tail into a `Vec` or a `Box`
+    if !options.no_fst {
+        let fst_vis = fst_struct.vis.clone();
+        let (tail_type, move_tail) = match &dst_type {
+            Type::Slice(slice) => {
+                let elem = &slice.elem;
+                (quote!(std::vec::Vec<#elem>), quote!(
+                    let len = tail.len();
+                    let mut vec = std::vec::Vec::with_capacity(len);
+                    core::ptr::copy_nonoverlapping(tail as *const #elem, vec.as_mut_ptr(), len);
+                    vec.set_len(len);
+                    vec
+                ))
+            }
+            _ => (quote!(std::boxed::Box<#dst_type>), quote!(
+                let init = dst_init::RawInitializer::new(
+                    core::alloc::Layout::for_value(&*tail),
+                    |ptr: core::ptr::NonNull<u8>| {
+                        core::ptr::copy_nonoverlapping(tail as *const u8, ptr.as_ptr(), core::mem::size_of_val(&*tail));
+                        core::ptr::NonNull::from_raw_parts(ptr, core::ptr::metadata(tail))
+                    },
+                );
+                <std::boxed::Box<#dst_type> as dst_init::BoxExt>::emplace(init)
+            )),
+        };
+        let members:Vec<Member> = fst_struct.fields.iter().enumerate()
+            .map(|(i,f)| f.ident.clone().map_or(Member::Unnamed(Index::from(i)), Member::Named))
+            .collect();
+        let (_, members) = members.split_last().unwrap();
+        let binds:Vec<Ident> = (0..members.len()).map(|i| Ident::new(&format!("f{}",i), Span::mixed_site())).collect();
+        let tail = Ident::new("tail", Span::mixed_site());
+        constructors.extend(quote!(
+            /// Borrow the header and the tail separately.
+            #[inline(always)]
+            #fst_vis fn split(&self) -> (&#fst_ident<#struct_generics_arg>, &#dst_type){
+                let base = self as *const Self as *const #fst_ident<#struct_generics_arg>;
+                // the trailing padding of the header may overlap the tail, which is fine for
+                // shared borrows
+                unsafe { (&*base, &self.#dst_field) }
+            }
+
+            /// Borrow the fields of the header and the tail separately and mutably.
+            #[inline(always)]
+            #fst_vis fn split_mut(&mut self) -> (#fst_mut_ident<'_, #struct_generics_arg>, &mut #dst_type){
+                let Self { #(#members: #binds,)* #dst_field: #tail } = self;
+                (#fst_mut_ident { #(#members: #binds,)* #dst_field: core::marker::PhantomData }, #tail)
+            }
+
+            /// Take the value apart into its header and its tail, without cloning.
+            #[inline(always)]
+            #fst_vis fn into_parts(self: std::boxed::Box<Self>) -> (#fst_ident<#struct_generics_arg>, #tail_type){unsafe{
+                let layout = core::alloc::Layout::for_value(&*self);
+                let raw = std::boxed::Box::into_raw(self);
+                let fst = core::ptr::read(raw as *const #fst_ident<#struct_generics_arg>);
+                let tail = core::ptr::addr_of_mut!((*raw).#dst_field);
+                let tail = { #move_tail };
+                if layout.size() != 0 {
+                    std::alloc::dealloc(raw as *mut u8, layout);
+                }
+                (fst, tail)
+            }}
+        ));
+    }
+
+    let impl_slice_tail = match &dst_type {
+        Type::Slice(slice) => {
+            let elem = &slice.elem;
+            quote!(
+                unsafe impl<#struct_generics_param> dst_init::tail::SliceTail for #struct_name<#struct_generics_arg>
+                    #struct_where_clause
+                {
+                    type Elem = #elem;
+
+                    #[inline(always)]
+                    fn tail_layout(len: usize) -> core::result::Result<(core::alloc::Layout, usize), core::alloc::LayoutError>{
+                        #fst_prefix
+                        let (layout, offset) = fst_prefix.extend(core::alloc::Layout::array::<#elem>(len)?)?;
+                        core::result::Result::Ok((layout.pad_to_align(), offset))
+                    }
+                }
+            )
+        }
+        _ => quote!(),
+    };
+
+    let impl_constructors:ItemImpl = syn::parse(quote!(
+        impl<#struct_generics_param> #struct_name<#struct_generics_arg>
+            #struct_where_clause
+        {
+            #constructors
+        }
+    ).into()).unwrap();
+
+    // the bounds are higher-ranked so that the impl still compiles when they do not hold
+    let members:Vec<Member> = new_struct.fields.iter().enumerate()
+        .map(|(i,f)| f.ident.clone().map_or(Member::Unnamed(Index::from(i)), Member::Named))
+        .collect();
+    let members = &members[..members.len() - 1];
+    let mut clone_where = struct_where_clause.clone()
+        .unwrap_or_else(|| syn::parse(quote!(where).into()).unwrap());
+    for ty in types {
+        clone_where.predicates.push(syn::parse(quote!(for<'__dst> #ty: core::clone::Clone).into()).unwrap());
+    }
+    clone_where.predicates.push(syn::parse(quote!(for<'__dst> #dst_type: dst_init::DstClone).into()).unwrap());
+    let impl_clone:ItemImpl = syn::parse(quote!(
+        impl<#struct_generics_param> dst_init::DstClone for #struct_name<#struct_generics_arg>
+            #clone_where
+        {
+            #[inline(always)]
+            fn clone_init(&self) -> impl dst_init::EmplaceInitializer<Output = Self>{
+                #(let #names = core::clone::Clone::clone(&self.#members);)*
+                let #tail_name = dst_init::DstClone::clone_init(&self.#dst_field);
+                #init_construct
+            }
+        }
+    ).into()).unwrap();
+
     let impl_init:ItemImpl = syn::parse(quote!(
         impl<#struct_generics_param DstInit:dst_init::EmplaceInitializer<Output=#dst_type>> dst_init::Initializer<DstInit> for #struct_name<#struct_generics_arg>
             #struct_where_clause
@@ -184,11 +677,152 @@ pub fn dst(_attr:TokenStream, input:TokenStream) -> TokenStream{
         }
     ).into()).unwrap();
 
+    // `dst_init!` turns a literal of the struct into a call of this macro, which knows the tail
+    // field and the init type. The init type is resolved through `Initializer` when implemented.
+    let literal_macro = format_ident!("__dst_init_{}",struct_name);
+    let infer_args:Vec<proc_macro2::TokenStream> = new_struct.generics.params.iter()
+        .map(|p| match p {
+            GenericParam::Lifetime(_) => quote!('_),
+            _ => quote!(_),
+        })
+        .collect();
+    let infer_args = if infer_args.is_empty() { quote!() } else { quote!(<#(#infer_args),*>) };
+    let literal_path = if impl_initializer {
+        quote!(dst_init::Init::<$($prefix)* #struct_name #infer_args, _>)
+    } else {
+        quote!($($prefix)* #init_ident)
+    };
+    let literal_macro = quote!(
+        #[doc(hidden)]
+        #[allow(unused_macros)]
+        macro_rules! #literal_macro {
+            ([$($prefix:tt)*] $fields:tt) => {
+                dst_init::__private::dst_literal!([#literal_path] #dst_field $fields)
+            };
+        }
+        #[doc(hidden)]
+        #[allow(unused_imports)]
+        pub(crate) use #literal_macro as #struct_name;
+    );
+
     let mut output = new_struct.into_token_stream();
-    output.extend(fst_struct.into_token_stream());
+    if !options.no_fst {
+        output.extend(fst_struct.into_token_stream());
+        output.extend(quote!(#[doc = #fst_mut_doc] #[allow(dead_code)] #fst_mut_struct));
+    }
     output.extend(init_struct.into_token_stream());
     output.extend(impl_emplace.into_token_stream());
-    output.extend(impl_init.into_token_stream());
+    output.extend(impl_try_emplace.into_token_stream());
+    output.extend(impl_constructors.into_token_stream());
+    output.extend(impl_clone.into_token_stream());
+    output.extend(impl_slice_tail);
+    if impl_initializer {
+        output.extend(impl_init.into_token_stream());
+    }
+    output.extend(literal_macro);
 
     output.into()
 }
+
+/// Turn the expression of a tail into its initializer.
+fn tail_init(expr: Expr) -> syn::Result<proc_macro2::TokenStream> {
+    match expr {
+        Expr::Struct(item) => {
+            if let Some(rest) = &item.rest {
+                return Err(syn::Error::new(rest.span(), "struct update syntax is not supported by `dst_init!`"));
+            }
+            // `a::Foo { .. }` becomes `a::Foo!{ [a::] .. }`, the macro generated by `#[dst]`
+            let mut prefix = item.path.clone();
+            let name = prefix.segments.pop().unwrap().into_value().ident;
+            let fields = item.fields;
+            let fields = quote_spanned!(item.brace_token.span=> { #fields });
+            Ok(quote!(#prefix #name!{ [#prefix] #fields }))
+        }
+        Expr::Group(expr) => tail_init(*expr.expr),
+        expr => Ok(quote!({
+            #[allow(unused_imports)]
+            use dst_init::__private::{InitTail as _, IterTail as _};
+            (&&dst_init::__private::Tail::new(#expr)).tail_init()
+        })),
+    }
+}
+
+/// Create the initializer of a dynamic sized value from a literal-like expression:
+/// ```rust
+/// # #![feature(ptr_metadata)]
+/// # use dst_init::{dst, dst_init, BoxExt};
+/// # use std::rc::Rc;
+/// #[dst]
+/// struct Packet(u32, [u8]);
+///
+/// #[dst]
+/// struct Frame {
+///     seq: u64,
+///     packet: Packet,
+/// }
+///
+/// let len = 2;
+/// let p: Rc<Packet> = Rc::emplace(dst_init!(Packet { 0: 1, 1: vec![0u8; len] }));
+/// let f: Box<Frame> = Box::emplace(dst_init!(Frame { packet: Packet { 1: [1, 2], 0: 3 }, seq: 4 }));
+/// assert_eq!((p.0, &p.1, f.seq, f.packet.0, &f.packet.1), (1, &[0, 0][..], 4, 3, &[1, 2][..]));
+/// ```
+/// - A struct literal `Foo { .. }` of a `#[dst]` struct becomes its `Init` struct, the fields can
+///   be in any order. Tuple structs are written with the braces too, like `Packet { 0: 1, 1: .. }`.
+/// - A tail which is an `EmplaceInitializer` is used as is.
+/// - Any other tail is iterated, like array literals, `vec![..]` and iterators with known
+///   length, and emplaced as a slice.
+///
+/// A tail in parentheses is never taken as a literal, like `(MyInitializer { .. })`.
+///
+/// The literal of `Foo` is expanded by a `macro_rules!` which `#[dst]` defines next to `Foo`, it
+/// is only visible in the crate of `Foo`. A `#[dst]` struct of another crate can not be written
+/// as a literal, use its `Init` struct there, like `FooInit { a: 1, b: dst_init!([2, 3]) }`.
+#[proc_macro]
+pub fn dst_init(input:TokenStream) -> TokenStream{
+    let expr:Expr = match syn::parse(input) {
+        Ok(expr) => expr,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    match tail_init(expr) {
+        Ok(init) => init.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// The literal of the `Init` struct, called by the macro `#[dst]` generates for `dst_init!`:
+/// `[init path] tail_member { fields.. }`.
+#[doc(hidden)]
+#[proc_macro]
+pub fn __dst_literal(input:TokenStream) -> TokenStream{
+    let parser = |input: ParseStream| {
+        let path;
+        syn::bracketed!(path in input);
+        let path: proc_macro2::TokenStream = path.parse()?;
+        let tail: Member = input.parse()?;
+        let fields;
+        let brace = syn::braced!(fields in input);
+        let fields = Punctuated::<FieldValue, Comma>::parse_terminated(&fields)?;
+        Ok((path, tail, brace, fields))
+    };
+    let (path, tail, brace, fields) = match parser.parse(input) {
+        Ok(parsed) => parsed,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    if !fields.iter().any(|f| f.member == tail) {
+        let tail = match &tail {
+            Member::Named(i) => i.to_string(),
+            Member::Unnamed(i) => i.index.to_string(),
+        };
+        let msg = format!("missing the tail field `{}`", tail);
+        return syn::Error::new(brace.span, msg).to_compile_error().into();
+    }
+    let fields = fields.into_iter().map(|f| {
+        let (member, expr) = (f.member, f.expr);
+        if member == tail {
+            quote_spanned!(expr.span()=> #member: dst_init::dst_init!(#expr))
+        } else {
+            quote!(#member: #expr)
+        }
+    });
+    quote!(#path { #(#fields,)* }).into()
+}
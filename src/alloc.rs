@@ -1,9 +1,14 @@
-use crate::EmplaceInitializer;
+use crate::{alloc_emplace_with_header, emplace_with_header, ArcInner, EmplaceInitializer, RcInner};
 use std::alloc::{alloc, AllocError, Allocator};
+use std::ptr;
 use std::ptr::NonNull;
+use std::rc::Rc;
+use std::sync::Arc;
 
 /// Extension for allocators to support `emplace(initializer)` method
 pub trait EmplaceAllocator {
+    /// Allocate memory for value and emplace in it. Returns the initializer back if the size of
+    /// the value overflows or the allocation failed.
     fn emplace<Init: EmplaceInitializer>(
         &self,
         init: Init,
@@ -17,7 +22,10 @@ impl<T: Allocator> EmplaceAllocator for T {
         &self,
         mut init: Init,
     ) -> Result<NonNull<Init::Output>, (AllocError, Init)> {
-        match self.allocate(init.layout()) {
+        let Ok(layout) = init.layout() else {
+            return Err((AllocError, init));
+        };
+        match self.allocate(layout) {
             Ok(mem) => Ok(init.emplace(mem.cast())),
             Err(e) => Err((e, init)),
         }
@@ -25,13 +33,236 @@ impl<T: Allocator> EmplaceAllocator for T {
 }
 
 /// Allocate memory for value by `std::alloc::alloc` and emplace in it.
+///
+/// Zero sized values are emplaced at a dangling, well-aligned pointer without allocation.
+///
+/// # Safety
+/// The returned memory is owned by the caller and must be released with `std::alloc::dealloc`
+/// using the layout of the emplaced value, unless the value is zero sized.
 #[inline(always)]
 pub unsafe fn alloc_emplace<Init: EmplaceInitializer>(
     mut init: Init,
 ) -> Result<NonNull<Init::Output>, Init> {
-    let mem = alloc(init.layout());
+    let Ok(layout) = init.layout() else {
+        return Err(init);
+    };
+    if layout.size() == 0 {
+        let mem = NonNull::new_unchecked(ptr::without_provenance_mut(layout.align()));
+        return Ok(init.emplace(mem));
+    }
+    let mem = alloc(layout);
     let Some(mem) = NonNull::new(mem) else{
         return Err(init);
     };
     Ok(init.emplace(mem))
 }
+
+/// Abstract for type `Box<T, A>`,`Rc<T, A>` and etc to allocate value by EmplaceInitializer types
+/// in a given allocator.
+pub trait BoxInExt<A: Allocator>: Sized {
+    type Output: ?Sized;
+
+    /// Allocate memory by `alloc` and emplace value in it, then use Self wrap it with the
+    /// allocator. Allocation failure is reported by `std::alloc::handle_alloc_error()`.
+    ///
+    /// # Panics
+    /// would panic if the size of the value overflows, use `try_emplace_in` to handle it.
+    fn emplace_in<Init: EmplaceInitializer<Output = Self::Output>>(init: Init, alloc: A) -> Self;
+
+    /// Allocate memory by `alloc` and emplace value in it, then use Self wrap it with the
+    /// allocator. Returns the initializer back if the size of the value overflows or the
+    /// allocation failed.
+    fn try_emplace_in<Init: EmplaceInitializer<Output = Self::Output>>(
+        init: Init,
+        alloc: A,
+    ) -> Result<Self, (AllocError, Init)>;
+}
+
+impl<T: ?Sized, A: Allocator> BoxInExt<A> for Box<T, A> {
+    type Output = T;
+
+    #[inline(always)]
+    fn emplace_in<Init: EmplaceInitializer<Output = Self::Output>>(init: Init, alloc: A) -> Self {
+        unsafe {
+            let obj = emplace_with_header(&alloc, (), init);
+            Box::from_raw_in(obj.as_ptr(), alloc)
+        }
+    }
+
+    #[inline(always)]
+    fn try_emplace_in<Init: EmplaceInitializer<Output = Self::Output>>(
+        init: Init,
+        alloc: A,
+    ) -> Result<Self, (AllocError, Init)> {
+        unsafe {
+            let obj = alloc_emplace_with_header(&alloc, (), init)?;
+            Ok(Box::from_raw_in(obj.as_ptr(), alloc))
+        }
+    }
+}
+
+impl<T: ?Sized, A: Allocator> BoxInExt<A> for Rc<T, A> {
+    type Output = T;
+
+    #[inline(always)]
+    fn emplace_in<Init: EmplaceInitializer<Output = Self::Output>>(init: Init, alloc: A) -> Self {
+        unsafe {
+            let obj = emplace_with_header(&alloc, RcInner::header(), init);
+            Rc::from_raw_in(obj.as_ptr(), alloc)
+        }
+    }
+
+    #[inline(always)]
+    fn try_emplace_in<Init: EmplaceInitializer<Output = Self::Output>>(
+        init: Init,
+        alloc: A,
+    ) -> Result<Self, (AllocError, Init)> {
+        unsafe {
+            let obj = alloc_emplace_with_header(&alloc, RcInner::header(), init)?;
+            Ok(Rc::from_raw_in(obj.as_ptr(), alloc))
+        }
+    }
+}
+
+impl<T: ?Sized, A: Allocator> BoxInExt<A> for Arc<T, A> {
+    type Output = T;
+
+    #[inline(always)]
+    fn emplace_in<Init: EmplaceInitializer<Output = Self::Output>>(init: Init, alloc: A) -> Self {
+        unsafe {
+            let obj = emplace_with_header(&alloc, ArcInner::header(), init);
+            Arc::from_raw_in(obj.as_ptr(), alloc)
+        }
+    }
+
+    #[inline(always)]
+    fn try_emplace_in<Init: EmplaceInitializer<Output = Self::Output>>(
+        init: Init,
+        alloc: A,
+    ) -> Result<Self, (AllocError, Init)> {
+        unsafe {
+            let obj = alloc_emplace_with_header(&alloc, ArcInner::header(), init)?;
+            Ok(Arc::from_raw_in(obj.as_ptr(), alloc))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BoxInExt;
+    use crate::{CoercionInitializer, Slice, SliceExt, SliceFnInitializer, SliceIterInitializer, SliceZeroedInitializer};
+    use std::alloc::{AllocError, Allocator, Global, Layout};
+    use std::cell::Cell;
+    use std::fmt::Debug;
+    use std::ptr::NonNull;
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    /// Counts the live and the zeroed allocations made through it, fails when `fail` is set.
+    #[derive(Default)]
+    struct CountingAlloc {
+        live: Cell<usize>,
+        zeroed: Cell<usize>,
+        fail: bool,
+    }
+
+    unsafe impl Allocator for CountingAlloc {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            if self.fail {
+                return Err(AllocError);
+            }
+            self.live.set(self.live.get() + 1);
+            Global.allocate(layout)
+        }
+
+        fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            self.zeroed.set(self.zeroed.get() + 1);
+            let mem = self.allocate(layout)?;
+            unsafe { mem.cast::<u8>().write_bytes(0, layout.size()) };
+            Ok(mem)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.live.set(self.live.get() - 1);
+            Global.deallocate(ptr, layout)
+        }
+    }
+
+    #[test]
+    fn test_emplace_in() {
+        let alloc = CountingAlloc::default();
+
+        let data: Box<[usize], _> = Box::emplace_in(SliceIterInitializer::new(4, 0..), &alloc);
+        assert_eq!(*data, [0, 1, 2, 3]);
+        assert_eq!(alloc.live.get(), 1);
+
+        let rc: Rc<[usize], _> = Rc::emplace_in(SliceFnInitializer::new(3, || 7), &alloc);
+        let weak = Rc::downgrade(&rc);
+        assert_eq!(*rc.clone(), [7, 7, 7]);
+        assert_eq!(alloc.live.get(), 2);
+
+        let arc: Arc<dyn Debug, _> = Arc::emplace_in(CoercionInitializer::new(5u8), &alloc);
+        assert_eq!(format!("{:?}", arc.clone()), "5");
+        assert_eq!(alloc.live.get(), 3);
+
+        drop(data);
+        drop(arc);
+        drop(rc);
+        assert_eq!(alloc.live.get(), 1);
+        drop(weak);
+        assert_eq!(alloc.live.get(), 0);
+    }
+
+    #[test]
+    fn test_try_emplace_in() {
+        let alloc = CountingAlloc::default();
+        let Ok(data) = Box::try_emplace_in(SliceIterInitializer::new(2, 0u8..), &alloc) else {
+            panic!("allocation should succeed");
+        };
+        assert_eq!(*data, [0, 1]);
+        drop(data);
+        assert_eq!(alloc.live.get(), 0);
+
+        let failing = CountingAlloc { fail: true, ..Default::default() };
+        let init = SliceIterInitializer::new(2, 0u8..);
+        let Err((AllocError, init)) = Rc::<[u8], _>::try_emplace_in(init, &failing) else {
+            panic!("allocation should fail");
+        };
+        let Err((AllocError, init)) = Arc::<[u8], _>::try_emplace_in(init, &failing) else {
+            panic!("allocation should fail");
+        };
+        let Ok(data): Result<Arc<[u8], _>, _> = Arc::try_emplace_in(init, &alloc) else {
+            panic!("allocation should succeed");
+        };
+        assert_eq!(*data, [0, 1]);
+
+        let init = SliceIterInitializer::new(usize::MAX, 0u64..);
+        assert!(Box::<[u64], _>::try_emplace_in(init, &alloc).is_err());
+    }
+
+    #[test]
+    fn test_emplace_in_zero_sized() {
+        let alloc = CountingAlloc::default();
+        let data: Box<[u64], _> = Box::emplace_in(SliceIterInitializer::new(0, 0..), &alloc);
+        assert_eq!((data.len(), alloc.live.get()), (0, 0));
+        let Ok(unit): Result<Box<dyn Debug, _>, _> = Box::try_emplace_in(CoercionInitializer::new(()), &alloc) else {
+            panic!("zero sized values need no allocation");
+        };
+        let zeroed: Box<[u32], _> = Box::emplace_in(Slice::zeroed(0), &alloc);
+        drop((data, unit, zeroed));
+        assert_eq!((alloc.live.get(), alloc.zeroed.get()), (0, 0));
+    }
+
+    #[test]
+    fn test_emplace_in_zeroed() {
+        let alloc = CountingAlloc::default();
+        let data: Box<[u32], _> = Box::emplace_in(SliceZeroedInitializer::new(5), &alloc);
+        assert_eq!((&*data, alloc.zeroed.get()), (&[0; 5][..], 1));
+        let rc: Rc<[u8], _> = Rc::emplace_in(SliceZeroedInitializer::new(3), &alloc);
+        assert_eq!((&*rc, Rc::strong_count(&rc), alloc.zeroed.get()), (&[0; 3][..], 1, 2));
+        let data: Box<[u16], _> = Box::emplace_in(Slice::zeroed(2), &alloc);
+        assert_eq!((&*data, alloc.zeroed.get()), (&[0; 2][..], 3));
+        let data: Box<[u8], _> = Box::emplace_in(SliceFnInitializer::new(2, || 0), &alloc);
+        assert_eq!((&*data, alloc.zeroed.get()), (&[0; 2][..], 3));
+    }
+}
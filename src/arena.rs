@@ -0,0 +1,281 @@
+//! A bump arena emplacing dynamic sized values.
+use crate::alloc::EmplaceAllocator;
+use crate::EmplaceInitializer;
+use std::alloc::{handle_alloc_error, AllocError, Allocator, Global, Layout};
+use std::cell::{Cell, RefCell};
+use std::marker::PhantomData;
+use std::ptr::{self, NonNull};
+
+const CHUNK_SIZE: usize = 4096;
+const CHUNK_ALIGN: usize = 16;
+
+/// A bump arena allocating values in chunks and handing out references living as long as the
+/// arena. Values emplaced by `emplace` are dropped with the arena, in reverse order.
+///
+/// The arena implements `Allocator`, so it also works with `EmplaceAllocator` and the
+/// `*_in` constructors of std. Deallocation through it is a no-op.
+///```rust
+/// #![feature(ptr_metadata)]
+/// use std::fmt::Debug;
+/// use dst_init::{CoercionInitializer, Slice, SliceExt};
+/// use dst_init::arena::DstArena;
+///
+/// let arena = DstArena::new();
+/// let slice: &mut [usize] = arena.emplace(Slice::iter_init(3, 0..));
+/// let debug: &mut dyn Debug = arena.emplace(CoercionInitializer::new(1u8));
+/// slice[0] = 5;
+/// assert_eq!(slice, &[5, 1, 2]);
+/// assert_eq!(format!("{:?}", debug), "1");
+///```
+pub struct DstArena<'a> {
+    ptr: Cell<NonNull<u8>>,
+    remaining: Cell<usize>,
+    chunk_size: Cell<usize>,
+    chunks: RefCell<Vec<(NonNull<u8>, Layout)>>,
+    drops: RefCell<Vec<(NonNull<u8>, DropFn)>>,
+    // invariant, so values with shorter lifetimes can not be emplaced through a coerced reference
+    phan: PhantomData<*mut &'a ()>,
+}
+
+type DropFn = unsafe fn(NonNull<u8>);
+
+/// Drop the value pointed by the pointer stored at `slot`.
+unsafe fn drop_slot<T: ?Sized>(slot: NonNull<u8>) {
+    ptr::drop_in_place(slot.cast::<*mut T>().read())
+}
+
+impl<'a> DstArena<'a> {
+    /// Create an empty arena without allocation.
+    pub fn new() -> Self {
+        Self::with_chunk_size(CHUNK_SIZE)
+    }
+
+    /// Create an empty arena whose first chunk has `chunk_size` bytes. Later chunks are twice as
+    /// large as the previous one, or large enough for the value.
+    pub fn with_chunk_size(chunk_size: usize) -> Self {
+        Self {
+            ptr: Cell::new(NonNull::dangling()),
+            remaining: Cell::new(0),
+            chunk_size: Cell::new(chunk_size.max(1)),
+            chunks: RefCell::new(Vec::new()),
+            drops: RefCell::new(Vec::new()),
+            phan: PhantomData,
+        }
+    }
+
+    /// Number of bytes of all allocated chunks.
+    pub fn allocated_bytes(&self) -> usize {
+        self.chunks.borrow().iter().map(|(_, layout)| layout.size()).sum()
+    }
+
+    /// Bump memory of `layout` in the current chunk, or in a new chunk if there is no room.
+    fn bump(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        if layout.size() == 0 {
+            return unsafe { Ok(NonNull::new_unchecked(ptr::without_provenance_mut(layout.align()))) };
+        }
+        let ptr = self.ptr.get();
+        let start = ptr.as_ptr().align_offset(layout.align());
+        if let Some(end) = start.checked_add(layout.size()) {
+            if end <= self.remaining.get() {
+                unsafe {
+                    self.ptr.set(ptr.add(end));
+                    self.remaining.set(self.remaining.get() - end);
+                    return Ok(ptr.add(start));
+                }
+            }
+        }
+        let size = layout
+            .size()
+            .checked_add(layout.align())
+            .ok_or(AllocError)?
+            .max(self.chunk_size.get());
+        let chunk = Layout::from_size_align(size, CHUNK_ALIGN.max(layout.align()))
+            .map_err(|_| AllocError)?;
+        let mem = Global.allocate(chunk)?.cast::<u8>();
+        self.chunks.borrow_mut().push((mem, chunk));
+        self.chunk_size.set(self.chunk_size.get().saturating_mul(2));
+        unsafe {
+            self.ptr.set(mem.add(layout.size()));
+            self.remaining.set(chunk.size() - layout.size());
+        }
+        Ok(mem)
+    }
+
+    /// Emplace value in the arena without registering its drop glue. Use it for values with
+    /// nothing to drop, or whose drop can be skipped.
+    ///
+    /// # Panics
+    /// would panic if the size of the value overflows, use `try_emplace_no_drop` to handle it.
+    #[allow(clippy::mut_from_ref)]
+    pub fn emplace_no_drop<Init: EmplaceInitializer>(&self, init: Init) -> &mut Init::Output
+    where
+        Init::Output: 'a,
+    {
+        match self.try_emplace_no_drop(init) {
+            Ok(obj) => obj,
+            Err((_, mut init)) => handle_alloc_error(init.layout().unwrap()),
+        }
+    }
+
+    /// Emplace value in the arena without registering its drop glue. Returns the initializer back
+    /// if the size of the value overflows or the allocation failed.
+    #[allow(clippy::mut_from_ref)]
+    pub fn try_emplace_no_drop<Init: EmplaceInitializer>(
+        &self,
+        init: Init,
+    ) -> Result<&mut Init::Output, (AllocError, Init)>
+    where
+        Init::Output: 'a,
+    {
+        unsafe { Ok(&mut *EmplaceAllocator::emplace(self, init)?.as_ptr()) }
+    }
+
+    /// Emplace value in the arena, it is dropped when the arena is dropped.
+    ///
+    /// # Panics
+    /// would panic if the size of the value overflows, use `try_emplace` to handle it.
+    #[allow(clippy::mut_from_ref)]
+    pub fn emplace<Init: EmplaceInitializer>(&self, init: Init) -> &mut Init::Output
+    where
+        Init::Output: 'a,
+    {
+        match self.try_emplace(init) {
+            Ok(obj) => obj,
+            Err((_, mut init)) => handle_alloc_error(init.layout().unwrap()),
+        }
+    }
+
+    /// Emplace value in the arena, it is dropped when the arena is dropped. Returns the
+    /// initializer back if the size of the value overflows or the allocation failed.
+    #[allow(clippy::mut_from_ref)]
+    pub fn try_emplace<Init: EmplaceInitializer>(
+        &self,
+        init: Init,
+    ) -> Result<&mut Init::Output, (AllocError, Init)>
+    where
+        Init::Output: 'a,
+    {
+        // the slot is taken first, so the value is never left without its drop glue
+        let slot = match self.bump(Layout::new::<*mut Init::Output>()) {
+            Ok(slot) => slot,
+            Err(e) => return Err((e, init)),
+        };
+        self.drops.borrow_mut().reserve(1);
+        let obj = EmplaceAllocator::emplace(self, init)?;
+        unsafe {
+            slot.cast::<*mut Init::Output>().write(obj.as_ptr());
+            self.drops
+                .borrow_mut()
+                .push((slot, drop_slot::<Init::Output>));
+            Ok(&mut *obj.as_ptr())
+        }
+    }
+}
+
+impl Default for DstArena<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl Allocator for DstArena<'_> {
+    #[inline(always)]
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let mem = self.bump(layout)?;
+        Ok(NonNull::slice_from_raw_parts(mem, layout.size()))
+    }
+
+    #[inline(always)]
+    unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {}
+}
+
+impl Drop for DstArena<'_> {
+    fn drop(&mut self) {
+        // a panicking drop leaks the chunks, the remaining values are not dropped
+        while let Some((slot, drop)) = self.drops.get_mut().pop() {
+            unsafe { drop(slot) }
+        }
+        for (mem, layout) in self.chunks.get_mut().drain(..) {
+            unsafe { Global.deallocate(mem, layout) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DstArena;
+    use crate::{self as dst_init, CoercionInitializer, SliceFnInitializer, SliceIterInitializer};
+    use dst_init_macros::dst;
+    use std::alloc::AllocError;
+    use std::cell::Cell;
+    use std::fmt::{Debug, Formatter};
+
+    #[dst]
+    struct Node {
+        id: u32,
+        edges: [u32],
+    }
+
+    struct DropCounter<'a>(&'a Cell<usize>);
+
+    impl Drop for DropCounter<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[repr(align(64))]
+    struct OverAligned(u8);
+
+    impl Debug for OverAligned {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    #[test]
+    fn test_arena() {
+        let arena = DstArena::with_chunk_size(64);
+        let nodes: Vec<&mut Node> = (0..20)
+            .map(|id| {
+                arena.emplace_no_drop(NodeInit {
+                    id,
+                    edges: SliceIterInitializer::new(id as usize % 4, 0..),
+                })
+            })
+            .collect();
+        for (i, node) in nodes.iter().enumerate() {
+            assert_eq!(node.id, i as u32);
+            assert_eq!(node.edges.len(), i % 4);
+        }
+        let big: &mut [u64] = arena.emplace(SliceFnInitializer::new(100, || 1));
+        assert_eq!(big.iter().sum::<u64>(), 100);
+
+        let aligned: &mut dyn Debug = arena.emplace(CoercionInitializer::new(OverAligned(3)));
+        assert_eq!(aligned as *mut dyn Debug as *mut u8 as usize % 64, 0);
+        assert_eq!(format!("{:?}", aligned), "3");
+        let empty: &mut [()] = arena.emplace(SliceFnInitializer::new(5, || ()));
+        assert_eq!(empty.len(), 5);
+
+        let init = SliceIterInitializer::new(usize::MAX, 0u64..);
+        assert!(matches!(arena.try_emplace(init), Err((AllocError, _))));
+
+        let boxed = Box::new_in([1u8, 2, 3], &arena);
+        assert_eq!(*boxed, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_arena_drop() {
+        let dropped = &Cell::new(0);
+        let arena = DstArena::new();
+        for len in 0..4 {
+            arena.emplace(SliceFnInitializer::new(len, || DropCounter(dropped)));
+        }
+        arena.emplace_no_drop(SliceFnInitializer::new(2, || DropCounter(dropped)));
+        let value: &mut [DropCounter] = arena.emplace(CoercionInitializer::new([DropCounter(dropped)]));
+        assert_eq!(value.len(), 1);
+        assert_eq!(dropped.get(), 0);
+        drop(arena);
+        assert_eq!(dropped.get(), 7);
+    }
+}
@@ -1,6 +1,5 @@
 //! A library for rust to provide ways to emplace dynamic sized type
 //! ```rust
-//! #![feature(alloc_layout_extra)]
 //! #![feature(ptr_metadata)]
 //!
 //! use dst_init_macros::dst;
@@ -38,18 +37,32 @@
 //! ```
 #![feature(ptr_metadata)]
 #![feature(unsize)]
-#![feature(alloc_layout_extra)]
 #![feature(allocator_api)]
+#![feature(trusted_len)]
+#![feature(min_specialization)]
 
 pub mod alloc;
+pub mod arena;
+pub mod stack;
+pub mod tail;
+pub mod thin;
+pub mod vec;
 
 pub use dst_init_macros as macros;
-pub use macros::dst;
-use std::alloc::Layout;
+pub use macros::{dst, dst_init};
+use std::alloc::{handle_alloc_error, AllocError, Allocator, Global, Layout, LayoutError};
+use std::borrow::Cow;
+use std::convert::Infallible;
+use std::cell::Cell;
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::iter::TrustedLen;
 use std::marker::{PhantomData, Unsize};
+use std::mem::MaybeUninit;
 use std::ptr::{null, NonNull, Pointee};
 use std::{mem, ptr};
 use std::rc::Rc;
+use std::sync::atomic::AtomicUsize;
 use std::sync::Arc;
 
 type Metadata<T> = <T as Pointee>::Metadata;
@@ -70,12 +83,21 @@ pub type Init<T, DstInit> = <T as Initializer<DstInit>>::Init;
 /// An abstract interface for all emplace initializer
 pub trait EmplaceInitializer {
     type Output: ?Sized;
-    /// Layout of the type
-    fn layout(&mut self) -> Layout;
+    /// Layout of the type, or error if the size of the type overflows
+    fn layout(&mut self) -> Result<Layout, LayoutError>;
     /// Emplace the type in given memory
     fn emplace(self, ptr: NonNull<u8>) -> NonNull<Self::Output>;
 }
 
+/// An abstract interface for emplace initializer which could fail while emplacing.
+/// `EmplaceInitializer::emplace` of such initializer panics on error.
+pub trait TryEmplaceInitializer: EmplaceInitializer {
+    type Error;
+    /// Emplace the type in given memory. On error, everything already written in the memory
+    /// has been dropped, and the memory itself is left to the caller.
+    fn try_emplace(self, ptr: NonNull<u8>) -> Result<NonNull<Self::Output>, Self::Error>;
+}
+
 /// An Emplace Initializer for Slice, created by iterator and member number.
 pub struct SliceIterInitializer<Iter: Iterator> {
     size: usize,
@@ -98,24 +120,23 @@ impl<Iter: Iterator> EmplaceInitializer for SliceIterInitializer<Iter> {
     type Output = [Iter::Item];
 
     #[inline(always)]
-    fn layout(&mut self) -> Layout {
-        Layout::array::<Iter::Item>(self.size).unwrap()
+    fn layout(&mut self) -> Result<Layout, LayoutError> {
+        Layout::array::<Iter::Item>(self.size)
     }
 
     #[inline(always)]
     fn emplace(mut self, ptr: NonNull<u8>) -> NonNull<Self::Output> {
-        unsafe {
-            let mut p: *mut Iter::Item = ptr.as_ptr().cast();
-            for _ in 0..self.size {
-                let item = self.iter.next().unwrap();
-                p.write(item);
-                p = p.add(1);
-            }
-            mem::transmute(NonNull::slice_from_raw_parts(
-                ptr.cast::<Iter::Item>(),
-                self.size,
-            ))
-        }
+        let iter = &mut self.iter;
+        unsafe { emplace_slice(ptr.cast(), self.size, || iter.next().unwrap()) }
+    }
+}
+
+impl<Iter: Iterator> TryEmplaceInitializer for SliceIterInitializer<Iter> {
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn try_emplace(self, ptr: NonNull<u8>) -> Result<NonNull<Self::Output>, Self::Error> {
+        Ok(self.emplace(ptr))
     }
 }
 
@@ -138,203 +159,1450 @@ impl<Item, F: FnMut() -> Item> EmplaceInitializer for SliceFnInitializer<Item, F
     type Output = [Item];
 
     #[inline(always)]
-    fn layout(&mut self) -> Layout {
-        Layout::array::<Item>(self.size).unwrap()
+    fn layout(&mut self) -> Result<Layout, LayoutError> {
+        Layout::array::<Item>(self.size)
+    }
+
+    #[inline(always)]
+    fn emplace(self, ptr: NonNull<u8>) -> NonNull<Self::Output> {
+        unsafe { emplace_slice(ptr.cast(), self.size, self.f) }
+    }
+}
+
+impl<Item, F: FnMut() -> Item> TryEmplaceInitializer for SliceFnInitializer<Item, F> {
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn try_emplace(self, ptr: NonNull<u8>) -> Result<NonNull<Self::Output>, Self::Error> {
+        Ok(self.emplace(ptr))
+    }
+}
+
+/// An Emplace Initializer for Slice, created by closure of the index and member number.
+pub struct SliceIndexedFnInitializer<Item, F: FnMut(usize) -> Item> {
+    size: usize,
+    f: F,
+}
+
+impl<Item, F: FnMut(usize) -> Item> SliceIndexedFnInitializer<Item, F> {
+    /// Create a SliceIndexedFnInitializer by closure and member number. Given closure will be
+    /// called with the indices from `0` to the member number in order
+    #[inline(always)]
+    pub fn new(size: usize, f: F) -> Self {
+        Self { size, f }
+    }
+}
+
+impl<Item, F: FnMut(usize) -> Item> EmplaceInitializer for SliceIndexedFnInitializer<Item, F> {
+    type Output = [Item];
+
+    #[inline(always)]
+    fn layout(&mut self) -> Result<Layout, LayoutError> {
+        Layout::array::<Item>(self.size)
     }
 
     #[inline(always)]
     fn emplace(mut self, ptr: NonNull<u8>) -> NonNull<Self::Output> {
+        let mut index = 0;
         unsafe {
-            let mut p: *mut Item = ptr.as_ptr().cast();
-            for _ in 0..self.size {
-                let item = (self.f)();
-                p.write(item);
-                p = p.add(1);
-            }
-            NonNull::slice_from_raw_parts(ptr.cast::<Item>(), self.size)
+            emplace_slice(ptr.cast(), self.size, || {
+                index += 1;
+                (self.f)(index - 1)
+            })
         }
     }
 }
 
-/// An Emplace Initializer for `dyn` or `[T]` types, created by concrete type `T` or `[T;N]`.
-/// For example `usize` is sized type and implemented `Debug`:
+impl<Item, F: FnMut(usize) -> Item> TryEmplaceInitializer for SliceIndexedFnInitializer<Item, F> {
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn try_emplace(self, ptr: NonNull<u8>) -> Result<NonNull<Self::Output>, Self::Error> {
+        Ok(self.emplace(ptr))
+    }
+}
+
+/// An Emplace Initializer for Slice, created by closure writing each item in place and member
+/// number. The closure gets the index and the uninitialized slot of the item, and returns the
+/// slot once initialized, like `MaybeUninit::write` does.
 ///```rust
-/// use std::fmt::Debug;
-/// use dst_init::{BoxExt, CoercionInitializer};
+/// use std::mem::MaybeUninit;
+/// use dst_init::{BoxExt, Slice, SliceExt};
 ///
-/// let init:CoercionInitializer<usize,dyn Debug> = CoercionInitializer::new(1usize);
-/// let boxed:Box<dyn Debug> = Box::emplace(init);
-/// assert_eq!(format!("{:?}",boxed),"1")
+/// let rows: Box<[[u64; 64]]> = Box::emplace(Slice::from_fn_uninit(2, |i, slot: &mut MaybeUninit<[u64; 64]>| {
+///     let array = slot.as_mut_ptr().cast::<u64>();
+///     for j in 0..64 {
+///         unsafe { array.add(j).write((i * 64 + j) as u64) };
+///     }
+///     unsafe { slot.assume_init_mut() }
+/// }));
+/// assert_eq!(rows[1][63], 127);
 ///```
-pub struct CoercionInitializer<T: Unsize<U>, U: ?Sized> {
-    t: T,
-    phan: PhantomData<U>,
+pub struct SliceUninitFnInitializer<Item, F>
+where
+    F: for<'s> FnMut(usize, &'s mut MaybeUninit<Item>) -> &'s mut Item,
+{
+    size: usize,
+    f: F,
+    phan: PhantomData<Item>,
 }
 
-impl<T: Unsize<U>, U: ?Sized> CoercionInitializer<T, U> {
+impl<Item, F> SliceUninitFnInitializer<Item, F>
+where
+    F: for<'s> FnMut(usize, &'s mut MaybeUninit<Item>) -> &'s mut Item,
+{
+    /// Create a SliceUninitFnInitializer by closure and member number. Given closure will be
+    /// called with the indices from `0` to the member number in order
+    ///
+    /// # Panics
+    /// `emplace` would panic if the closure returns a reference other than the given slot.
     #[inline(always)]
-    pub fn new(t: T) -> Self {
-        Self {
-            t,
-            phan: Default::default(),
+    pub fn new(size: usize, f: F) -> Self {
+        Self { size, f, phan: PhantomData }
+    }
+}
+
+impl<Item, F> EmplaceInitializer for SliceUninitFnInitializer<Item, F>
+where
+    F: for<'s> FnMut(usize, &'s mut MaybeUninit<Item>) -> &'s mut Item,
+{
+    type Output = [Item];
+
+    #[inline(always)]
+    fn layout(&mut self) -> Result<Layout, LayoutError> {
+        Layout::array::<Item>(self.size)
+    }
+
+    #[inline(always)]
+    fn emplace(mut self, ptr: NonNull<u8>) -> NonNull<Self::Output> {
+        let mut guard = SliceGuard { ptr: ptr.cast::<Item>(), len: 0 };
+        while guard.len < self.size {
+            unsafe {
+                let slot = guard.ptr.as_ptr().add(guard.len);
+                let item = (self.f)(guard.len, &mut *slot.cast::<MaybeUninit<Item>>());
+                // the slot is initialized only if the closure gives it back
+                assert!(ptr::eq(item, slot), "the closure must return the slot it initialized");
+            }
+            guard.len += 1;
         }
+        mem::forget(guard);
+        NonNull::slice_from_raw_parts(ptr.cast(), self.size)
     }
+}
+
+impl<Item, F> TryEmplaceInitializer for SliceUninitFnInitializer<Item, F>
+where
+    F: for<'s> FnMut(usize, &'s mut MaybeUninit<Item>) -> &'s mut Item,
+{
+    type Error = Infallible;
+
     #[inline(always)]
-    pub fn fallback(self) -> T {
-        self.t
+    fn try_emplace(self, ptr: NonNull<u8>) -> Result<NonNull<Self::Output>, Self::Error> {
+        Ok(self.emplace(ptr))
     }
 }
 
-impl<T: Unsize<U>, U: ?Sized> EmplaceInitializer for CoercionInitializer<T, U> {
-    type Output = U;
+/// A fallible Emplace Initializer for Slice, created by iterator of `Result` and member number.
+pub struct SliceTryIterInitializer<Iter: Iterator> {
+    size: usize,
+    iter: Iter,
+}
+
+impl<T, E, Iter: Iterator<Item = Result<T, E>>> SliceTryIterInitializer<Iter> {
+    /// Create a SliceTryIterInitializer by iterator and member number. iterator::next will be
+    /// called for given member number times, or until it yields an error.
+    ///
+    /// # Panics
+    /// would panic if iterator has less item than given member number
+    #[inline(always)]
+    pub fn new(size: usize, iter: Iter) -> Self {
+        Self { size, iter }
+    }
+}
+
+impl<T, E, Iter: Iterator<Item = Result<T, E>>> EmplaceInitializer
+    for SliceTryIterInitializer<Iter>
+{
+    type Output = [T];
 
     #[inline(always)]
-    fn layout(&mut self) -> Layout {
-        Layout::new::<T>()
+    fn layout(&mut self) -> Result<Layout, LayoutError> {
+        Layout::array::<T>(self.size)
     }
 
+    /// # Panics
+    /// would panic if iterator yields an error
     #[inline(always)]
     fn emplace(self, ptr: NonNull<u8>) -> NonNull<Self::Output> {
-        unsafe {
-            let meta = metadata_of::<T, U>();
-            ptr.as_ptr().cast::<T>().write(self.t);
-            NonNull::from_raw_parts(ptr, meta)
-        }
+        self.try_emplace(ptr)
+            .unwrap_or_else(|_| panic!("SliceTryIterInitializer failed"))
     }
 }
 
-/// An Emplace Initializer for sized type, created by itself.
-pub struct DirectInitializer<T> {
-    t: T,
+impl<T, E, Iter: Iterator<Item = Result<T, E>>> TryEmplaceInitializer
+    for SliceTryIterInitializer<Iter>
+{
+    type Error = E;
+
+    #[inline(always)]
+    fn try_emplace(mut self, ptr: NonNull<u8>) -> Result<NonNull<Self::Output>, Self::Error> {
+        let iter = &mut self.iter;
+        unsafe { try_emplace_slice(ptr.cast(), self.size, || iter.next().unwrap()) }
+    }
 }
 
-impl<T> DirectInitializer<T> {
+/// A fallible Emplace Initializer for Slice, created by closure and member number.
+pub struct SliceTryFnInitializer<T, E, F: FnMut() -> Result<T, E>> {
+    size: usize,
+    f: F,
+}
+
+impl<T, E, F: FnMut() -> Result<T, E>> SliceTryFnInitializer<T, E, F> {
+    /// Create a SliceTryFnInitializer by closure and member number. Given closure will be called
+    /// for given member number times, or until it returns an error.
     #[inline(always)]
-    pub fn new(t: T) -> Self {
-        Self { t }
+    pub fn new(size: usize, f: F) -> Self {
+        Self { size, f }
+    }
+}
+
+impl<T, E, F: FnMut() -> Result<T, E>> EmplaceInitializer for SliceTryFnInitializer<T, E, F> {
+    type Output = [T];
+
+    #[inline(always)]
+    fn layout(&mut self) -> Result<Layout, LayoutError> {
+        Layout::array::<T>(self.size)
     }
 
+    /// # Panics
+    /// would panic if closure returns an error
     #[inline(always)]
-    pub fn fallback(self) -> T {
-        self.t
+    fn emplace(self, ptr: NonNull<u8>) -> NonNull<Self::Output> {
+        self.try_emplace(ptr)
+            .unwrap_or_else(|_| panic!("SliceTryFnInitializer failed"))
     }
 }
 
-impl<T> EmplaceInitializer for DirectInitializer<T> {
-    type Output = T;
+impl<T, E, F: FnMut() -> Result<T, E>> TryEmplaceInitializer for SliceTryFnInitializer<T, E, F> {
+    type Error = E;
+
+    #[inline(always)]
+    fn try_emplace(self, ptr: NonNull<u8>) -> Result<NonNull<Self::Output>, Self::Error> {
+        unsafe { try_emplace_slice(ptr.cast(), self.size, self.f) }
+    }
+}
+
+/// An Emplace Initializer for Slice, created by a `TrustedLen` iterator whose length is the
+/// member number. The items are written by internal iteration, without checking the length.
+pub struct SliceTrustedLenInitializer<Iter: TrustedLen> {
+    size: usize,
+    iter: Iter,
+}
+
+impl<Iter: TrustedLen> SliceTrustedLenInitializer<Iter> {
+    /// # Panics
+    /// would panic if the iterator has more than `usize::MAX` items.
+    #[inline(always)]
+    pub fn new(iter: Iter) -> Self {
+        let size = iter.size_hint().1.expect("the iterator has more than usize::MAX items");
+        Self { size, iter }
+    }
+}
+
+impl<Iter: TrustedLen> EmplaceInitializer for SliceTrustedLenInitializer<Iter> {
+    type Output = [Iter::Item];
 
     #[inline(always)]
-    fn layout(&mut self) -> Layout {
-        Layout::new::<T>()
+    fn layout(&mut self) -> Result<Layout, LayoutError> {
+        Layout::array::<Iter::Item>(self.size)
     }
 
     #[inline(always)]
     fn emplace(self, ptr: NonNull<u8>) -> NonNull<Self::Output> {
-        unsafe {
-            ptr.as_ptr().cast::<T>().write(self.t);
-            ptr.cast()
+        let mut guard = SliceGuard { ptr: ptr.cast::<Iter::Item>(), len: 0 };
+        // `TrustedLen` guarantees exactly `size` items
+        self.iter.for_each(|item| unsafe {
+            guard.ptr.as_ptr().add(guard.len).write(item);
+            guard.len += 1;
+        });
+        mem::forget(guard);
+        NonNull::slice_from_raw_parts(ptr.cast(), self.size)
+    }
+}
+
+impl<Iter: TrustedLen> TryEmplaceInitializer for SliceTrustedLenInitializer<Iter> {
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn try_emplace(self, ptr: NonNull<u8>) -> Result<NonNull<Self::Output>, Self::Error> {
+        Ok(self.emplace(ptr))
+    }
+}
+
+/// Error of `SliceCheckedIterInitializer`, the iterator does not have the member number of items.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IterLengthError {
+    /// The iterator ended after `found` items, less than `expected`.
+    Short { expected: usize, found: usize },
+    /// The iterator has more items than `expected`.
+    Long { expected: usize },
+}
+
+impl Display for IterLengthError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            IterLengthError::Short { expected, found } => {
+                write!(f, "iterator has {found} items, expected {expected}")
+            }
+            IterLengthError::Long { expected } => {
+                write!(f, "iterator has more than {expected} items")
+            }
         }
     }
 }
 
-/// Abstract for type `Box`,`Rc` and etc to allocate value by EmplaceInitializer types.
-pub trait BoxExt: Sized {
+impl Error for IterLengthError {}
 
-    type Output: ?Sized;
+/// An Emplace Initializer for Slice, created by iterator and member number. Unlike
+/// `SliceIterInitializer`, `try_emplace` fails with `IterLengthError` if the iterator has less or
+/// more items than the member number.
+pub struct SliceCheckedIterInitializer<Iter: Iterator> {
+    size: usize,
+    iter: Iter,
+}
 
-    /// Allocate memory by `std::alloc::alloc()` and emplace value in it
-    /// Then use Self wrap it.
-    fn emplace<Init: EmplaceInitializer<Output = Self::Output>>(init: Init) -> Self;
+impl<Iter: Iterator> SliceCheckedIterInitializer<Iter> {
+    #[inline(always)]
+    pub fn new(size: usize, iter: Iter) -> Self {
+        Self { size, iter }
+    }
 }
 
-impl<T: ?Sized> BoxExt for Box<T> {
+impl<Iter: Iterator> EmplaceInitializer for SliceCheckedIterInitializer<Iter> {
+    type Output = [Iter::Item];
 
-    type Output = T;
+    #[inline(always)]
+    fn layout(&mut self) -> Result<Layout, LayoutError> {
+        Layout::array::<Iter::Item>(self.size)
+    }
 
-    /// Allocate memory by `std::alloc::alloc()` and emplace value in it
-    /// Then use `Box` wrap it.
-    fn emplace<Init: EmplaceInitializer<Output = Self::Output>>(
-        mut init: Init,
-    ) -> Box<Self::Output> {
-        unsafe {
-            let layout = init.layout();
-            let mem = std::alloc::alloc(layout);
-            let obj = init.emplace(NonNull::new(mem).unwrap());
-            Box::from_raw(obj.as_ptr())
+    /// # Panics
+    /// would panic if the iterator has less or more items than the member number.
+    #[inline(always)]
+    fn emplace(self, ptr: NonNull<u8>) -> NonNull<Self::Output> {
+        match self.try_emplace(ptr) {
+            Ok(obj) => obj,
+            Err(e) => panic!("{e}"),
         }
     }
 }
 
-impl<T: ?Sized> BoxExt for Rc<T> {
-    type Output = T;
+impl<Iter: Iterator> TryEmplaceInitializer for SliceCheckedIterInitializer<Iter> {
+    type Error = IterLengthError;
 
-    /// Allocate memory by `std::alloc::alloc()` and emplace value in it
-    /// Then use `Rc` wrap it.
-    fn emplace<Init: EmplaceInitializer<Output = Self::Output>>(
-        mut init: Init,
-    ) -> Rc<Self::Output> {
+    #[inline(always)]
+    fn try_emplace(mut self, ptr: NonNull<u8>) -> Result<NonNull<Self::Output>, Self::Error> {
+        let (expected, iter) = (self.size, &mut self.iter);
+        let mut found = 0;
         unsafe {
-            let layout = init.layout();
-            let mem = std::alloc::alloc(layout);
-            let obj = init.emplace(NonNull::new(mem).unwrap());
-            Rc::from_raw(obj.as_ptr())
+            let obj = try_emplace_slice(ptr.cast(), expected, || match iter.next() {
+                Some(item) => {
+                    found += 1;
+                    Ok(item)
+                }
+                None => Err(IterLengthError::Short { expected, found }),
+            })?;
+            if self.iter.next().is_some() {
+                ptr::drop_in_place(obj.as_ptr());
+                return Err(IterLengthError::Long { expected });
+            }
+            Ok(obj)
         }
     }
 }
 
-impl<T: ?Sized> BoxExt for Arc<T> {
-    type Output = T;
+/// An Emplace Initializer for Slice, filled with clones of a value. The value itself is moved
+/// into the last item.
+pub struct SliceRepeatInitializer<T: Clone> {
+    size: usize,
+    value: T,
+}
 
-    /// Allocate memory by `std::alloc::alloc()` and emplace value in it
-    /// Then use `Arc` wrap it.
-    fn emplace<Init: EmplaceInitializer<Output = Self::Output>>(
-        mut init: Init,
-    ) -> Arc<Self::Output> {
+impl<T: Clone> SliceRepeatInitializer<T> {
+    #[inline(always)]
+    pub fn new(size: usize, value: T) -> Self {
+        Self { size, value }
+    }
+
+    #[inline(always)]
+    pub fn fallback(self) -> T {
+        self.value
+    }
+}
+
+impl<T: Clone> EmplaceInitializer for SliceRepeatInitializer<T> {
+    type Output = [T];
+
+    #[inline(always)]
+    fn layout(&mut self) -> Result<Layout, LayoutError> {
+        Layout::array::<T>(self.size)
+    }
+
+    #[inline(always)]
+    fn emplace(self, ptr: NonNull<u8>) -> NonNull<Self::Output> {
+        let (size, mut value) = (self.size, Some(self.value));
+        let mut index = 0;
         unsafe {
-            let layout = init.layout();
-            let mem = std::alloc::alloc(layout);
-            let obj = init.emplace(NonNull::new(mem).unwrap());
-            Arc::from_raw(obj.as_ptr())
+            emplace_slice(ptr.cast(), size, || {
+                index += 1;
+                if index == size {
+                    value.take().unwrap()
+                } else {
+                    value.as_ref().unwrap().clone()
+                }
+            })
         }
     }
 }
 
-/// pub type Slice\<T\> = \[T\];
-pub type Slice<T> = [T];
+impl<T: Clone> TryEmplaceInitializer for SliceRepeatInitializer<T> {
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn try_emplace(self, ptr: NonNull<u8>) -> Result<NonNull<Self::Output>, Self::Error> {
+        Ok(self.emplace(ptr))
+    }
+}
+
+/// Types whose value of all zero bytes is valid.
+///
+/// # Safety
+/// The all zero bit pattern must be a valid value of the type.
+pub unsafe trait Zeroable {}
+
+macro_rules! impl_zeroable {
+    ($($ty:ty),*) => {
+        $(unsafe impl Zeroable for $ty {})*
+    };
+}
+
+impl_zeroable!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, bool, char, ());
+
+unsafe impl<T: Zeroable, const N: usize> Zeroable for [T; N] {}
+unsafe impl<T> Zeroable for MaybeUninit<T> {}
+unsafe impl<T: ?Sized> Zeroable for PhantomData<T> {}
+unsafe impl<T> Zeroable for *const T {}
+unsafe impl<T> Zeroable for *mut T {}
+
+/// An Emplace Initializer for Slice of zero bytes. Emplaced as a whole value, the memory is
+/// allocated zeroed, otherwise it is written by `write_bytes`.
+// `T: Zeroable` is only required by `new`, so that `ZeroedEmplace` can be specialized for it
+pub struct SliceZeroedInitializer<T> {
+    size: usize,
+    phan: PhantomData<T>,
+}
+
+impl<T: Zeroable> SliceZeroedInitializer<T> {
+    #[inline(always)]
+    pub fn new(size: usize) -> Self {
+        Self { size, phan: PhantomData }
+    }
+}
+
+impl<T> EmplaceInitializer for SliceZeroedInitializer<T> {
+    type Output = [T];
+
+    #[inline(always)]
+    fn layout(&mut self) -> Result<Layout, LayoutError> {
+        Layout::array::<T>(self.size)
+    }
+
+    #[inline(always)]
+    fn emplace(self, ptr: NonNull<u8>) -> NonNull<Self::Output> {
+        unsafe { ptr.as_ptr().cast::<T>().write_bytes(0, self.size) };
+        self.emplace_zeroed(ptr)
+    }
+
+}
+
+impl<T> ZeroedEmplace for SliceZeroedInitializer<T> {
+    #[inline(always)]
+    fn zeroed() -> bool {
+        true
+    }
+
+    #[inline(always)]
+    fn emplace_zeroed(self, ptr: NonNull<u8>) -> NonNull<Self::Output> {
+        NonNull::slice_from_raw_parts(ptr.cast(), self.size)
+    }
+}
+
+impl<T> TryEmplaceInitializer for SliceZeroedInitializer<T> {
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn try_emplace(self, ptr: NonNull<u8>) -> Result<NonNull<Self::Output>, Self::Error> {
+        Ok(self.emplace(ptr))
+    }
+}
+
+/// An Emplace Initializer for Slice of `Copy` items, copied from a slice by a single
+/// `copy_nonoverlapping`.
+pub struct SliceCopyInitializer<'a, T: Copy> {
+    src: &'a [T],
+}
+
+impl<'a, T: Copy> SliceCopyInitializer<'a, T> {
+    #[inline(always)]
+    pub fn new(src: &'a [T]) -> Self {
+        Self { src }
+    }
+}
+
+impl<T: Copy> EmplaceInitializer for SliceCopyInitializer<'_, T> {
+    type Output = [T];
+
+    #[inline(always)]
+    fn layout(&mut self) -> Result<Layout, LayoutError> {
+        Ok(Layout::for_value(self.src))
+    }
+
+    #[inline(always)]
+    fn emplace(self, ptr: NonNull<u8>) -> NonNull<Self::Output> {
+        unsafe {
+            ptr::copy_nonoverlapping(self.src.as_ptr(), ptr.as_ptr().cast::<T>(), self.src.len());
+            NonNull::slice_from_raw_parts(ptr.cast(), self.src.len())
+        }
+    }
+}
+
+impl<T: Copy> TryEmplaceInitializer for SliceCopyInitializer<'_, T> {
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn try_emplace(self, ptr: NonNull<u8>) -> Result<NonNull<Self::Output>, Self::Error> {
+        Ok(self.emplace(ptr))
+    }
+}
+
+/// An Emplace Initializer for Slice, cloning the items of a slice. If a clone panics, the items
+/// already cloned are dropped.
+pub struct SliceCloneInitializer<'a, T: Clone> {
+    src: &'a [T],
+}
+
+impl<'a, T: Clone> SliceCloneInitializer<'a, T> {
+    #[inline(always)]
+    pub fn new(src: &'a [T]) -> Self {
+        Self { src }
+    }
+}
+
+impl<T: Clone> EmplaceInitializer for SliceCloneInitializer<'_, T> {
+    type Output = [T];
+
+    #[inline(always)]
+    fn layout(&mut self) -> Result<Layout, LayoutError> {
+        Ok(Layout::for_value(self.src))
+    }
+
+    #[inline(always)]
+    fn emplace(self, ptr: NonNull<u8>) -> NonNull<Self::Output> {
+        let mut iter = self.src.iter();
+        unsafe { emplace_slice(ptr.cast(), self.src.len(), || iter.next().unwrap().clone()) }
+    }
+}
+
+impl<T: Clone> TryEmplaceInitializer for SliceCloneInitializer<'_, T> {
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn try_emplace(self, ptr: NonNull<u8>) -> Result<NonNull<Self::Output>, Self::Error> {
+        Ok(self.emplace(ptr))
+    }
+}
+
+/// An Emplace Initializer for Slice, moving the items out of a `Vec` or a boxed slice by a single
+/// `copy_nonoverlapping`. The buffer of the source is freed without dropping the moved items.
+pub struct SliceVecInitializer<T> {
+    vec: Vec<T>,
+}
+
+impl<T> SliceVecInitializer<T> {
+    #[inline(always)]
+    pub fn new(vec: Vec<T>) -> Self {
+        Self { vec }
+    }
+
+    #[inline(always)]
+    pub fn fallback(self) -> Vec<T> {
+        self.vec
+    }
+}
+
+impl<T> From<Vec<T>> for SliceVecInitializer<T> {
+    #[inline(always)]
+    fn from(vec: Vec<T>) -> Self {
+        Self::new(vec)
+    }
+}
+
+impl<T> From<Box<[T]>> for SliceVecInitializer<T> {
+    #[inline(always)]
+    fn from(slice: Box<[T]>) -> Self {
+        Self::new(slice.into_vec())
+    }
+}
+
+impl<T> EmplaceInitializer for SliceVecInitializer<T> {
+    type Output = [T];
+
+    #[inline(always)]
+    fn layout(&mut self) -> Result<Layout, LayoutError> {
+        Ok(Layout::for_value::<[T]>(&self.vec))
+    }
+
+    #[inline(always)]
+    fn emplace(mut self, ptr: NonNull<u8>) -> NonNull<Self::Output> {
+        let len = self.vec.len();
+        unsafe {
+            ptr::copy_nonoverlapping(self.vec.as_ptr(), ptr.as_ptr().cast::<T>(), len);
+            self.vec.set_len(0);
+            NonNull::slice_from_raw_parts(ptr.cast(), len)
+        }
+    }
+}
+
+impl<T> TryEmplaceInitializer for SliceVecInitializer<T> {
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn try_emplace(self, ptr: NonNull<u8>) -> Result<NonNull<Self::Output>, Self::Error> {
+        Ok(self.emplace(ptr))
+    }
+}
+
+/// Drops the items already written in a slice, unless forgotten.
+struct SliceGuard<T> {
+    ptr: NonNull<T>,
+    len: usize,
+}
+
+impl<T> Drop for SliceGuard<T> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.ptr.as_ptr(), self.len)) }
+    }
+}
+
+/// Write `size` items produced by `f` from `ptr`. If `f` panics, the items already
+/// written are dropped.
+#[inline(always)]
+unsafe fn emplace_slice<T>(ptr: NonNull<T>, size: usize, mut f: impl FnMut() -> T) -> NonNull<[T]> {
+    let mut guard = SliceGuard { ptr, len: 0 };
+    while guard.len < size {
+        ptr.as_ptr().add(guard.len).write(f());
+        guard.len += 1;
+    }
+    mem::forget(guard);
+    NonNull::slice_from_raw_parts(ptr, size)
+}
+
+/// Write `size` items produced by `f` from `ptr`. If `f` fails or panics, the items already
+/// written are dropped.
+#[inline(always)]
+unsafe fn try_emplace_slice<T, E>(
+    ptr: NonNull<T>,
+    size: usize,
+    mut f: impl FnMut() -> Result<T, E>,
+) -> Result<NonNull<[T]>, E> {
+    let mut guard = SliceGuard { ptr, len: 0 };
+    while guard.len < size {
+        ptr.as_ptr().add(guard.len).write(f()?);
+        guard.len += 1;
+    }
+    mem::forget(guard);
+    Ok(NonNull::slice_from_raw_parts(ptr, size))
+}
+
+/// Frees the memory of an emplacement, unless forgotten or zero sized.
+struct AllocGuard<'a, A: Allocator> {
+    ptr: NonNull<u8>,
+    layout: Layout,
+    alloc: &'a A,
+}
+
+impl<A: Allocator> Drop for AllocGuard<'_, A> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        if self.layout.size() != 0 {
+            unsafe { self.alloc.deallocate(self.ptr, self.layout) }
+        }
+    }
+}
+
+/// An Emplace Initializer for `dyn` or `[T]` types, created by concrete type `T` or `[T;N]`.
+/// For example `usize` is sized type and implemented `Debug`:
+///```rust
+/// use std::fmt::Debug;
+/// use dst_init::{BoxExt, CoercionInitializer};
+///
+/// let init:CoercionInitializer<usize,dyn Debug> = CoercionInitializer::new(1usize);
+/// let boxed:Box<dyn Debug> = Box::emplace(init);
+/// assert_eq!(format!("{:?}",boxed),"1")
+///```
+pub struct CoercionInitializer<T: Unsize<U>, U: ?Sized> {
+    t: T,
+    phan: PhantomData<U>,
+}
+
+impl<T: Unsize<U>, U: ?Sized> CoercionInitializer<T, U> {
+    #[inline(always)]
+    pub fn new(t: T) -> Self {
+        Self {
+            t,
+            phan: Default::default(),
+        }
+    }
+    #[inline(always)]
+    pub fn fallback(self) -> T {
+        self.t
+    }
+}
+
+impl<T: Unsize<U>, U: ?Sized> EmplaceInitializer for CoercionInitializer<T, U> {
+    type Output = U;
+
+    #[inline(always)]
+    fn layout(&mut self) -> Result<Layout, LayoutError> {
+        Ok(Layout::new::<T>())
+    }
+
+    #[inline(always)]
+    fn emplace(self, ptr: NonNull<u8>) -> NonNull<Self::Output> {
+        unsafe {
+            let meta = metadata_of::<T, U>();
+            ptr.as_ptr().cast::<T>().write(self.t);
+            NonNull::from_raw_parts(ptr, meta)
+        }
+    }
+}
+
+impl<T: Unsize<U>, U: ?Sized> TryEmplaceInitializer for CoercionInitializer<T, U> {
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn try_emplace(self, ptr: NonNull<u8>) -> Result<NonNull<Self::Output>, Self::Error> {
+        Ok(self.emplace(ptr))
+    }
+}
+
+/// An Emplace Initializer for sized type, created by itself.
+pub struct DirectInitializer<T> {
+    t: T,
+}
+
+impl<T> DirectInitializer<T> {
+    #[inline(always)]
+    pub fn new(t: T) -> Self {
+        Self { t }
+    }
+
+    #[inline(always)]
+    pub fn fallback(self) -> T {
+        self.t
+    }
+}
+
+impl<T> EmplaceInitializer for DirectInitializer<T> {
+    type Output = T;
+
+    #[inline(always)]
+    fn layout(&mut self) -> Result<Layout, LayoutError> {
+        Ok(Layout::new::<T>())
+    }
+
+    #[inline(always)]
+    fn emplace(self, ptr: NonNull<u8>) -> NonNull<Self::Output> {
+        unsafe {
+            ptr.as_ptr().cast::<T>().write(self.t);
+            ptr.cast()
+        }
+    }
+}
+
+impl<T> TryEmplaceInitializer for DirectInitializer<T> {
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn try_emplace(self, ptr: NonNull<u8>) -> Result<NonNull<Self::Output>, Self::Error> {
+        Ok(self.emplace(ptr))
+    }
+}
+
+/// An Emplace Initializer for `str`, copying a borrowed or owned string, or the output of
+/// `format_args!`.
+///```rust
+/// use std::rc::Rc;
+/// use dst_init::{BoxExt, StrInitializer};
+///
+/// let boxed: Box<str> = Box::emplace(StrInitializer::new("str"));
+/// let rc: Rc<str> = Rc::emplace(StrInitializer::from(format_args!("{}-{}", 1, 2)));
+/// assert_eq!((&*boxed, &*rc), ("str", "1-2"));
+///```
+pub struct StrInitializer<'a> {
+    s: Cow<'a, str>,
+}
+
+impl<'a> StrInitializer<'a> {
+    #[inline(always)]
+    pub fn new(s: impl Into<Cow<'a, str>>) -> Self {
+        Self { s: s.into() }
+    }
+
+    #[inline(always)]
+    pub fn fallback(self) -> Cow<'a, str> {
+        self.s
+    }
+}
+
+impl<'a> From<&'a str> for StrInitializer<'a> {
+    #[inline(always)]
+    fn from(s: &'a str) -> Self {
+        Self::new(s)
+    }
+}
+
+impl From<String> for StrInitializer<'_> {
+    #[inline(always)]
+    fn from(s: String) -> Self {
+        Self::new(s)
+    }
+}
+
+impl From<fmt::Arguments<'_>> for StrInitializer<'_> {
+    /// Format the arguments, without allocation if they are a literal.
+    #[inline(always)]
+    fn from(args: fmt::Arguments<'_>) -> Self {
+        match args.as_str() {
+            Some(s) => Self::new(s),
+            None => Self::new(fmt::format(args)),
+        }
+    }
+}
+
+impl EmplaceInitializer for StrInitializer<'_> {
+    type Output = str;
+
+    #[inline(always)]
+    fn layout(&mut self) -> Result<Layout, LayoutError> {
+        Ok(Layout::for_value::<str>(&self.s))
+    }
+
+    #[inline(always)]
+    fn emplace(self, ptr: NonNull<u8>) -> NonNull<Self::Output> {
+        unsafe {
+            ptr::copy_nonoverlapping(self.s.as_ptr(), ptr.as_ptr(), self.s.len());
+            NonNull::from_raw_parts(ptr, self.s.len())
+        }
+    }
+}
+
+impl TryEmplaceInitializer for StrInitializer<'_> {
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn try_emplace(self, ptr: NonNull<u8>) -> Result<NonNull<Self::Output>, Self::Error> {
+        Ok(self.emplace(ptr))
+    }
+}
+
+/// Error of emplacing a value by `BoxExt::try_emplace`.
+pub enum EmplaceError<Init, E> {
+    /// The size of the value overflows, the initializer is given back.
+    Layout(LayoutError, Init),
+    /// The memory allocation failed, the initializer is given back.
+    Alloc(AllocError, Init),
+    /// The initializer failed.
+    Init(E),
+}
+
+impl<Init, E> EmplaceError<Init, E> {
+    /// Take back the initializer if it was not consumed.
+    #[inline(always)]
+    pub fn into_init(self) -> Option<Init> {
+        match self {
+            EmplaceError::Layout(_, init) | EmplaceError::Alloc(_, init) => Some(init),
+            EmplaceError::Init(_) => None,
+        }
+    }
+}
+
+impl<Init, E: Debug> Debug for EmplaceError<Init, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            EmplaceError::Layout(e, _) => f.debug_tuple("Layout").field(e).finish_non_exhaustive(),
+            EmplaceError::Alloc(e, _) => f.debug_tuple("Alloc").field(e).finish_non_exhaustive(),
+            EmplaceError::Init(e) => f.debug_tuple("Init").field(e).finish(),
+        }
+    }
+}
+
+impl<Init, E: Display> Display for EmplaceError<Init, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            EmplaceError::Layout(e, _) => Display::fmt(e, f),
+            EmplaceError::Alloc(e, _) => Display::fmt(e, f),
+            EmplaceError::Init(e) => Display::fmt(e, f),
+        }
+    }
+}
+
+impl<Init, E: Error + 'static> Error for EmplaceError<Init, E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            EmplaceError::Layout(e, _) => Some(e),
+            EmplaceError::Alloc(e, _) => Some(e),
+            EmplaceError::Init(e) => Some(e),
+        }
+    }
+}
+
+/// Abstract for type `Box`,`Rc` and etc to allocate value by EmplaceInitializer types.
+pub trait BoxExt: Sized {
+
+    type Output: ?Sized;
+
+    /// Allocate memory by `std::alloc::alloc()` and emplace value in it
+    /// Then use Self wrap it. Zero sized values are not allocated, and allocation failure is
+    /// reported by `std::alloc::handle_alloc_error()`.
+    ///
+    /// # Panics
+    /// would panic if the size of the value overflows, use `try_emplace` to handle it.
+    fn emplace<Init: EmplaceInitializer<Output = Self::Output>>(init: Init) -> Self;
+
+    /// Allocate memory by `std::alloc::alloc()` and try to emplace value in it, then use Self
+    /// wrap it. If the size of the value overflows or the allocation fails, the initializer is
+    /// given back in the error. If the initializer fails, its error is returned and no memory
+    /// is leaked.
+    fn try_emplace<Init: TryEmplaceInitializer<Output = Self::Output>>(
+        init: Init,
+    ) -> Result<Self, EmplaceError<Init, Init::Error>>;
+}
+
+impl<T: ?Sized> BoxExt for Box<T> {
+
+    type Output = T;
+
+    /// Allocate memory by `std::alloc::alloc()` and emplace value in it
+    /// Then use `Box` wrap it.
+    fn emplace<Init: EmplaceInitializer<Output = Self::Output>>(
+        init: Init,
+    ) -> Box<Self::Output> {
+        unsafe {
+            let obj = emplace_with_header(&Global, (), init);
+            Box::from_raw(obj.as_ptr())
+        }
+    }
+
+    /// Allocate memory by `std::alloc::alloc()` and try to emplace value in it
+    /// Then use `Box` wrap it.
+    fn try_emplace<Init: TryEmplaceInitializer<Output = Self::Output>>(
+        init: Init,
+    ) -> Result<Box<Self::Output>, EmplaceError<Init, Init::Error>> {
+        unsafe {
+            let obj = try_emplace_with_header(&Global, (), init)?;
+            Ok(Box::from_raw(obj.as_ptr()))
+        }
+    }
+}
+
+/// Same layout as the allocation behind `Rc<T>`: both counters ahead of the value.
+/// `Rc::from_raw` relies on this `#[repr(C)]` layout to find the counters again.
+#[repr(C)]
+struct RcInner<T: ?Sized> {
+    strong: Cell<usize>,
+    weak: Cell<usize>,
+    value: T,
+}
+
+impl RcInner<()> {
+    /// Header of a new `Rc` with one strong reference.
+    #[inline(always)]
+    fn header() -> Self {
+        RcInner {
+            strong: Cell::new(1),
+            weak: Cell::new(1),
+            value: (),
+        }
+    }
+}
+
+/// Same layout as the allocation behind `Arc<T>`: both counters ahead of the value.
+/// `Arc::from_raw` relies on this `#[repr(C)]` layout to find the counters again.
+#[repr(C)]
+struct ArcInner<T: ?Sized> {
+    strong: AtomicUsize,
+    weak: AtomicUsize,
+    data: T,
+}
+
+impl ArcInner<()> {
+    /// Header of a new `Arc` with one strong reference.
+    #[inline(always)]
+    fn header() -> Self {
+        ArcInner {
+            strong: AtomicUsize::new(1),
+            weak: AtomicUsize::new(1),
+            data: (),
+        }
+    }
+}
+
+/// Layout of `Header` followed by the value of `init`, and the offset of the value.
+#[inline(always)]
+fn layout_with_header<Header, Init: EmplaceInitializer>(
+    init: &mut Init,
+) -> Result<(Layout, usize), LayoutError> {
+    let (layout, offset) = Layout::new::<Header>().extend(init.layout()?)?;
+    Ok((layout.pad_to_align(), offset))
+}
+
+/// Emplacing in zeroed memory, specialized for the initializers only writing zero bytes, like
+/// `SliceZeroedInitializer`, whose memory is allocated by `Allocator::allocate_zeroed`.
+trait ZeroedEmplace: EmplaceInitializer + Sized {
+    /// Whether `emplace_zeroed` must be given zeroed memory
+    fn zeroed() -> bool;
+    /// Emplace the type in given memory, whose bytes of the layout are already zero if `ZEROED`
+    fn emplace_zeroed(self, ptr: NonNull<u8>) -> NonNull<Self::Output>;
+}
+
+impl<Init: EmplaceInitializer> ZeroedEmplace for Init {
+    #[inline(always)]
+    default fn zeroed() -> bool {
+        false
+    }
+
+    #[inline(always)]
+    default fn emplace_zeroed(self, ptr: NonNull<u8>) -> NonNull<Self::Output> {
+        self.emplace(ptr)
+    }
+}
+
+/// Allocate memory of `layout` by `alloc`, zeroed if `Init` only writes zero bytes. Zero sized
+/// layouts get a dangling, well-aligned pointer without allocation, as `Box` never deallocates
+/// them.
+#[inline(always)]
+fn allocate<A: Allocator, Init: EmplaceInitializer>(alloc: &A, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+    if layout.size() == 0 {
+        return Ok(unsafe { NonNull::new_unchecked(ptr::without_provenance_mut(layout.align())) });
+    }
+    let mem = if Init::zeroed() {
+        alloc.allocate_zeroed(layout)?
+    } else {
+        alloc.allocate(layout)?
+    };
+    Ok(mem.cast())
+}
+
+/// Allocate memory by `alloc` for `header` followed by the value of `init`, write the header
+/// and emplace the value right after it. Returns the pointer to the value, or the initializer
+/// back if the layout overflows or the allocation fails.
+/// Zero sized layouts get a dangling pointer without allocation.
+#[inline(always)]
+unsafe fn alloc_emplace_with_header<A: Allocator, Header, Init: EmplaceInitializer>(
+    alloc: &A,
+    header: Header,
+    mut init: Init,
+) -> Result<NonNull<Init::Output>, (AllocError, Init)> {
+    let Ok((layout, offset)) = layout_with_header::<Header, _>(&mut init) else {
+        return Err((AllocError, init));
+    };
+    let mem = match allocate::<_, Init>(alloc, layout) {
+        Ok(mem) => mem,
+        Err(e) => return Err((e, init)),
+    };
+    let guard = AllocGuard { ptr: mem, layout, alloc };
+    mem.cast::<Header>().as_ptr().write(header);
+    let obj = if Init::zeroed() {
+        init.emplace_zeroed(mem.add(offset))
+    } else {
+        init.emplace(mem.add(offset))
+    };
+    mem::forget(guard);
+    Ok(obj)
+}
+
+/// Same as `alloc_emplace_with_header`, but panics if the layout overflows and reports
+/// allocation failure by `handle_alloc_error()`.
+#[inline(always)]
+unsafe fn emplace_with_header<A: Allocator, Header, Init: EmplaceInitializer>(
+    alloc: &A,
+    header: Header,
+    init: Init,
+) -> NonNull<Init::Output> {
+    match alloc_emplace_with_header(alloc, header, init) {
+        Ok(obj) => obj,
+        Err((_, mut init)) => {
+            let (layout, _) = layout_with_header::<Header, _>(&mut init).unwrap();
+            handle_alloc_error(layout)
+        }
+    }
+}
+
+/// Same as `emplace_with_header`, but with a fallible initializer. The initializer is given
+/// back if the layout overflows or the allocation fails. If the initializer fails, the memory
+/// is freed and the error is returned.
+#[inline(always)]
+unsafe fn try_emplace_with_header<A: Allocator, Header, Init: TryEmplaceInitializer>(
+    alloc: &A,
+    header: Header,
+    mut init: Init,
+) -> Result<NonNull<Init::Output>, EmplaceError<Init, Init::Error>> {
+    let (layout, offset) = match layout_with_header::<Header, _>(&mut init) {
+        Ok(layout) => layout,
+        Err(e) => return Err(EmplaceError::Layout(e, init)),
+    };
+    let mem = match allocate::<_, Init>(alloc, layout) {
+        Ok(mem) => mem,
+        Err(e) => return Err(EmplaceError::Alloc(e, init)),
+    };
+    let guard = AllocGuard { ptr: mem, layout, alloc };
+    mem.cast::<Header>().as_ptr().write(header);
+    let obj = if Init::zeroed() {
+        init.emplace_zeroed(mem.add(offset))
+    } else {
+        init.try_emplace(mem.add(offset)).map_err(EmplaceError::Init)?
+    };
+    mem::forget(guard);
+    Ok(obj)
+}
+
+impl<T: ?Sized> BoxExt for Rc<T> {
+    type Output = T;
+
+    /// Allocate memory by `std::alloc::alloc()` for the reference counters and the value,
+    /// emplace value in it. Then use `Rc` wrap it.
+    fn emplace<Init: EmplaceInitializer<Output = Self::Output>>(init: Init) -> Rc<Self::Output> {
+        unsafe {
+            let obj = emplace_with_header(&Global, RcInner::header(), init);
+            Rc::from_raw(obj.as_ptr())
+        }
+    }
+
+    /// Allocate memory by `std::alloc::alloc()` for the reference counters and the value,
+    /// try to emplace value in it. Then use `Rc` wrap it.
+    fn try_emplace<Init: TryEmplaceInitializer<Output = Self::Output>>(
+        init: Init,
+    ) -> Result<Rc<Self::Output>, EmplaceError<Init, Init::Error>> {
+        unsafe {
+            let obj = try_emplace_with_header(&Global, RcInner::header(), init)?;
+            Ok(Rc::from_raw(obj.as_ptr()))
+        }
+    }
+}
+
+impl<T: ?Sized> BoxExt for Arc<T> {
+    type Output = T;
+
+    /// Allocate memory by `std::alloc::alloc()` for the reference counters and the value,
+    /// emplace value in it. Then use `Arc` wrap it.
+    fn emplace<Init: EmplaceInitializer<Output = Self::Output>>(init: Init) -> Arc<Self::Output> {
+        unsafe {
+            let obj = emplace_with_header(&Global, ArcInner::header(), init);
+            Arc::from_raw(obj.as_ptr())
+        }
+    }
+
+    /// Allocate memory by `std::alloc::alloc()` for the reference counters and the value,
+    /// try to emplace value in it. Then use `Arc` wrap it.
+    fn try_emplace<Init: TryEmplaceInitializer<Output = Self::Output>>(
+        init: Init,
+    ) -> Result<Arc<Self::Output>, EmplaceError<Init, Init::Error>> {
+        unsafe {
+            let obj = try_emplace_with_header(&Global, ArcInner::header(), init)?;
+            Ok(Arc::from_raw(obj.as_ptr()))
+        }
+    }
+}
+
+/// pub type Slice\<T\> = \[T\];
+pub type Slice<T> = [T];
+
+/// Extension for type `[T]` to create EmplaceInitializer.
+pub trait SliceExt {
+    type Item;
+
+    /// create SliceFnInitializer
+    fn fn_init<F>(size: usize, f: F) -> impl EmplaceInitializer<Output = [Self::Item]>
+    where
+        F: FnMut() -> Self::Item;
+
+    /// create SliceIterInitializer
+    fn iter_init<Iter>(size: usize, iter: Iter) -> impl EmplaceInitializer<Output = [Self::Item]>
+    where
+        Iter: Iterator<Item = Self::Item>;
+
+    /// create SliceTryFnInitializer
+    fn try_fn_init<E, F>(
+        size: usize,
+        f: F,
+    ) -> impl TryEmplaceInitializer<Output = [Self::Item], Error = E>
+    where
+        F: FnMut() -> Result<Self::Item, E>;
+
+    /// create SliceTryIterInitializer
+    fn try_iter_init<E, Iter>(
+        size: usize,
+        iter: Iter,
+    ) -> impl TryEmplaceInitializer<Output = [Self::Item], Error = E>
+    where
+        Iter: Iterator<Item = Result<Self::Item, E>>;
+
+    /// create SliceCopyInitializer
+    fn copy_from(src: &[Self::Item]) -> impl EmplaceInitializer<Output = [Self::Item]>
+    where
+        Self::Item: Copy;
+
+    /// create SliceCloneInitializer
+    fn clone_from(src: &[Self::Item]) -> impl EmplaceInitializer<Output = [Self::Item]>
+    where
+        Self::Item: Clone;
+
+    /// create SliceVecInitializer from a `Vec`
+    fn from_vec(vec: Vec<Self::Item>) -> impl EmplaceInitializer<Output = [Self::Item]>;
+
+    /// create SliceVecInitializer from a boxed slice
+    fn from_boxed_slice(slice: Box<[Self::Item]>) -> impl EmplaceInitializer<Output = [Self::Item]>;
+
+    /// create SliceIterInitializer, the member number is the length of the iterator
+    fn from_exact_iter<I>(iter: I) -> impl EmplaceInitializer<Output = [Self::Item]>
+    where
+        I: IntoIterator<Item = Self::Item>,
+        I::IntoIter: ExactSizeIterator;
+
+    /// create SliceTrustedLenInitializer
+    fn from_trusted_len<I>(iter: I) -> impl EmplaceInitializer<Output = [Self::Item]>
+    where
+        I: IntoIterator<Item = Self::Item>,
+        I::IntoIter: TrustedLen;
+
+    /// create SliceCheckedIterInitializer
+    fn checked_iter_init<Iter>(
+        size: usize,
+        iter: Iter,
+    ) -> impl TryEmplaceInitializer<Output = [Self::Item], Error = IterLengthError>
+    where
+        Iter: Iterator<Item = Self::Item>;
+
+    /// create SliceIndexedFnInitializer
+    fn from_fn_indexed<F>(size: usize, f: F) -> impl EmplaceInitializer<Output = [Self::Item]>
+    where
+        F: FnMut(usize) -> Self::Item;
+
+    /// create SliceUninitFnInitializer
+    fn from_fn_uninit<F>(size: usize, f: F) -> impl EmplaceInitializer<Output = [Self::Item]>
+    where
+        F: for<'s> FnMut(usize, &'s mut MaybeUninit<Self::Item>) -> &'s mut Self::Item;
+
+    /// create SliceRepeatInitializer, replaces `repeat` as the inherent `[T]::repeat` shadows it
+    fn repeat_init(size: usize, value: Self::Item) -> impl EmplaceInitializer<Output = [Self::Item]>
+    where
+        Self::Item: Clone;
+
+    /// create SliceZeroedInitializer
+    fn zeroed(size: usize) -> impl EmplaceInitializer<Output = [Self::Item]>
+    where
+        Self::Item: Zeroable;
+}
+
+impl<T> SliceExt for Slice<T> {
+    type Item = T;
+
+    /// create SliceFnInitializer
+    #[inline(always)]
+    fn fn_init<F>(size: usize, f: F) -> impl EmplaceInitializer<Output = [Self::Item]>
+    where
+        F: FnMut() -> Self::Item,
+    {
+        SliceFnInitializer::new(size, f)
+    }
+
+    /// create SliceIterInitializer
+    fn iter_init<Iter>(size: usize, iter: Iter) -> impl EmplaceInitializer<Output = [Self::Item]>
+    where
+        Iter: Iterator<Item = Self::Item>,
+    {
+        SliceIterInitializer::new(size, iter)
+    }
+
+    /// create SliceTryFnInitializer
+    #[inline(always)]
+    fn try_fn_init<E, F>(
+        size: usize,
+        f: F,
+    ) -> impl TryEmplaceInitializer<Output = [Self::Item], Error = E>
+    where
+        F: FnMut() -> Result<Self::Item, E>,
+    {
+        SliceTryFnInitializer::new(size, f)
+    }
+
+    /// create SliceTryIterInitializer
+    #[inline(always)]
+    fn try_iter_init<E, Iter>(
+        size: usize,
+        iter: Iter,
+    ) -> impl TryEmplaceInitializer<Output = [Self::Item], Error = E>
+    where
+        Iter: Iterator<Item = Result<Self::Item, E>>,
+    {
+        SliceTryIterInitializer::new(size, iter)
+    }
+
+    /// create SliceCopyInitializer
+    #[inline(always)]
+    fn copy_from(src: &[Self::Item]) -> impl EmplaceInitializer<Output = [Self::Item]>
+    where
+        Self::Item: Copy,
+    {
+        SliceCopyInitializer::new(src)
+    }
+
+    /// create SliceCloneInitializer
+    #[inline(always)]
+    fn clone_from(src: &[Self::Item]) -> impl EmplaceInitializer<Output = [Self::Item]>
+    where
+        Self::Item: Clone,
+    {
+        SliceCloneInitializer::new(src)
+    }
+
+    /// create SliceVecInitializer from a `Vec`
+    #[inline(always)]
+    fn from_vec(vec: Vec<Self::Item>) -> impl EmplaceInitializer<Output = [Self::Item]> {
+        SliceVecInitializer::new(vec)
+    }
+
+    /// create SliceVecInitializer from a boxed slice
+    #[inline(always)]
+    fn from_boxed_slice(slice: Box<[Self::Item]>) -> impl EmplaceInitializer<Output = [Self::Item]> {
+        SliceVecInitializer::from(slice)
+    }
+
+    /// create SliceIterInitializer, the member number is the length of the iterator
+    #[inline(always)]
+    fn from_exact_iter<I>(iter: I) -> impl EmplaceInitializer<Output = [Self::Item]>
+    where
+        I: IntoIterator<Item = Self::Item>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let iter = iter.into_iter();
+        SliceIterInitializer::new(iter.len(), iter)
+    }
+
+    /// create SliceTrustedLenInitializer
+    #[inline(always)]
+    fn from_trusted_len<I>(iter: I) -> impl EmplaceInitializer<Output = [Self::Item]>
+    where
+        I: IntoIterator<Item = Self::Item>,
+        I::IntoIter: TrustedLen,
+    {
+        SliceTrustedLenInitializer::new(iter.into_iter())
+    }
+
+    /// create SliceCheckedIterInitializer
+    #[inline(always)]
+    fn checked_iter_init<Iter>(
+        size: usize,
+        iter: Iter,
+    ) -> impl TryEmplaceInitializer<Output = [Self::Item], Error = IterLengthError>
+    where
+        Iter: Iterator<Item = Self::Item>,
+    {
+        SliceCheckedIterInitializer::new(size, iter)
+    }
+
+    /// create SliceIndexedFnInitializer
+    #[inline(always)]
+    fn from_fn_indexed<F>(size: usize, f: F) -> impl EmplaceInitializer<Output = [Self::Item]>
+    where
+        F: FnMut(usize) -> Self::Item,
+    {
+        SliceIndexedFnInitializer::new(size, f)
+    }
+
+    /// create SliceUninitFnInitializer
+    #[inline(always)]
+    fn from_fn_uninit<F>(size: usize, f: F) -> impl EmplaceInitializer<Output = [Self::Item]>
+    where
+        F: for<'s> FnMut(usize, &'s mut MaybeUninit<Self::Item>) -> &'s mut Self::Item,
+    {
+        SliceUninitFnInitializer::new(size, f)
+    }
+
+    /// create SliceRepeatInitializer, replaces `repeat` as the inherent `[T]::repeat` shadows it
+    #[inline(always)]
+    fn repeat_init(size: usize, value: Self::Item) -> impl EmplaceInitializer<Output = [Self::Item]>
+    where
+        Self::Item: Clone,
+    {
+        SliceRepeatInitializer::new(size, value)
+    }
+
+    /// create SliceZeroedInitializer
+    #[inline(always)]
+    fn zeroed(size: usize) -> impl EmplaceInitializer<Output = [Self::Item]>
+    where
+        Self::Item: Zeroable,
+    {
+        SliceZeroedInitializer::new(size)
+    }
+}
+
+/// Clone dynamic sized values by emplacing a copy of them. `#[dst]` implements it for the struct
+/// when the fields before the tail are `Clone` and the tail is `DstClone`.
+///```rust
+/// use dst_init::DstClone;
+///
+/// let a: Box<[String]> = vec![String::from("a")].into_boxed_slice();
+/// assert_eq!(a.clone_rc(), a.clone_box().into());
+/// assert_eq!(&*"str".clone_arc(), "str");
+///```
+pub trait DstClone {
+    /// Initializer emplacing a clone of `self`.
+    fn clone_init(&self) -> impl EmplaceInitializer<Output = Self>;
 
-/// Extension for type `[T]` to create EmplaceInitializer.
-pub trait SliceExt {
-    type Item;
+    /// Clone `self` into a `Box`.
+    #[inline(always)]
+    fn clone_box(&self) -> Box<Self> {
+        Box::emplace(self.clone_init())
+    }
 
-    /// create SliceFnInitializer
-    fn fn_init<F>(size: usize, f: F) -> impl EmplaceInitializer<Output = [Self::Item]>
-    where
-        F: FnMut() -> Self::Item;
+    /// Clone `self` into a `Rc`.
+    #[inline(always)]
+    fn clone_rc(&self) -> Rc<Self> {
+        Rc::emplace(self.clone_init())
+    }
 
-    /// create SliceIterInitializer
-    fn iter_init<Iter>(size: usize, iter: Iter) -> impl EmplaceInitializer<Output = [Self::Item]>
-    where
-        Iter: Iterator<Item = Self::Item>;
+    /// Clone `self` into an `Arc`.
+    #[inline(always)]
+    fn clone_arc(&self) -> Arc<Self> {
+        Arc::emplace(self.clone_init())
+    }
 }
 
-impl<T> SliceExt for Slice<T> {
-    type Item = T;
-
-    /// create SliceFnInitializer
+impl<T: Clone> DstClone for [T] {
     #[inline(always)]
-    fn fn_init<F>(size: usize, f: F) -> impl EmplaceInitializer<Output = [Self::Item]>
-    where
-        F: FnMut() -> Self::Item,
-    {
-        SliceFnInitializer::new(size, f)
+    fn clone_init(&self) -> impl EmplaceInitializer<Output = Self> {
+        SliceCloneInitializer::new(self)
     }
+}
 
-    /// create SliceIterInitializer
-    fn iter_init<Iter>(size: usize, iter: Iter) -> impl EmplaceInitializer<Output = [Self::Item]>
-    where
-        Iter: Iterator<Item = Self::Item>,
-    {
-        SliceIterInitializer::new(size, iter)
+impl DstClone for str {
+    #[inline(always)]
+    fn clone_init(&self) -> impl EmplaceInitializer<Output = Self> {
+        StrInitializer::new(self)
     }
 }
 
@@ -361,8 +1629,8 @@ impl<Output, F> EmplaceInitializer for RawInitializer<Output, F>
 {
     type Output = Output;
 
-    fn layout(&mut self) -> Layout {
-        self.layout
+    fn layout(&mut self) -> Result<Layout, LayoutError> {
+        Ok(self.layout)
     }
 
     fn emplace(self, ptr: NonNull<u8>) -> NonNull<Self::Output> {
@@ -370,18 +1638,91 @@ impl<Output, F> EmplaceInitializer for RawInitializer<Output, F>
     }
 }
 
+impl<Output, F> TryEmplaceInitializer for RawInitializer<Output, F>
+    where Output:?Sized, F:FnOnce(NonNull<u8>)->NonNull<Output>
+{
+    type Error = Infallible;
+
+    fn try_emplace(self, ptr: NonNull<u8>) -> Result<NonNull<Self::Output>, Self::Error> {
+        Ok(self.emplace(ptr))
+    }
+}
+
+/// Support of the `dst_init!` macro, not public API.
+#[doc(hidden)]
+pub mod __private {
+    use crate::{EmplaceInitializer, SliceIterInitializer};
+    use std::cell::Cell;
+
+    pub use crate::macros::__dst_literal as dst_literal;
+
+    /// A tail expression of `dst_init!`, turned into an initializer by `InitTail` if it is one,
+    /// or by `IterTail` if it is an iterable of known length. Called as
+    /// `(&&Tail::new(expr)).tail_init()`, the method resolution picks `InitTail` first.
+    pub struct Tail<T>(Cell<Option<T>>);
+
+    impl<T> Tail<T> {
+        #[inline(always)]
+        pub fn new(t: T) -> Self {
+            Self(Cell::new(Some(t)))
+        }
+
+        #[inline(always)]
+        fn take(&self) -> T {
+            self.0.take().unwrap()
+        }
+    }
+
+    pub trait InitTail {
+        type Init;
+        fn tail_init(&self) -> Self::Init;
+    }
+
+    impl<Init: EmplaceInitializer> InitTail for &Tail<Init> {
+        type Init = Init;
+
+        #[inline(always)]
+        fn tail_init(&self) -> Init {
+            self.take()
+        }
+    }
+
+    pub trait IterTail {
+        type Init;
+        fn tail_init(&self) -> Self::Init;
+    }
+
+    impl<I: IntoIterator> IterTail for Tail<I>
+    where
+        I::IntoIter: ExactSizeIterator,
+    {
+        type Init = SliceIterInitializer<I::IntoIter>;
+
+        #[inline(always)]
+        fn tail_init(&self) -> Self::Init {
+            let iter = self.take().into_iter();
+            SliceIterInitializer::new(iter.len(), iter)
+        }
+    }
+}
+
 #[cfg(test)]
 pub mod test {
-    use crate::{self as dst_init, RawInitializer};
+    use crate::{self as dst_init, DstClone, RawInitializer, SliceCloneInitializer, SliceCopyInitializer, SliceVecInitializer};
+    use crate::{IterLengthError, SliceCheckedIterInitializer, SliceTrustedLenInitializer, StrInitializer};
+    use crate::{SliceIndexedFnInitializer, SliceRepeatInitializer, SliceUninitFnInitializer, SliceZeroedInitializer};
     use crate::{
-        CoercionInitializer, DirectInitializer, EmplaceInitializer,
-        SliceFnInitializer, SliceIterInitializer,
+        BoxExt, CoercionInitializer, DirectInitializer, EmplaceError, EmplaceInitializer, Slice, SliceExt,
+        SliceFnInitializer, SliceIterInitializer, SliceTryFnInitializer, SliceTryIterInitializer,
     };
-    use dst_init_macros::dst;
+    use dst_init_macros::{dst, dst_init};
     use std::alloc;
     use std::alloc::Layout;
+    use std::cell::Cell;
     use std::fmt::{Debug, Formatter};
     use std::ptr::NonNull;
+    use std::rc::Rc;
+    use std::sync::Arc;
 
     #[dst]
     #[derive(Debug)]
@@ -414,7 +1755,7 @@ pub mod test {
 
     fn alloc<O: ?Sized, Init: EmplaceInitializer<Output = O>>(mut init: Init) -> Box<O> {
         unsafe {
-            let layout = init.layout();
+            let layout = init.layout().unwrap();
             let ptr = alloc::alloc(layout);
             if ptr.is_null() {
                 panic!("no memory");
@@ -451,7 +1792,7 @@ pub mod test {
         fn test<T: PartialEq + Debug>(a: T, b: T) {
             let mut init = DirectInitializer::new(a);
             let layout = init.layout();
-            assert_eq!(layout, Layout::new::<T>());
+            assert_eq!(layout, Ok(Layout::new::<T>()));
             let obj = alloc(init);
             assert_eq!(*obj, b);
         }
@@ -507,6 +1848,40 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn test_slice_indexed_fn_initializer() {
+        let data: Box<[usize]> = Box::emplace(Slice::from_fn_indexed(100, |i| i * 2));
+        assert!(data.iter().enumerate().all(|(i, x)| *x == i * 2));
+        let packet: Rc<Packet> = Rc::emplace(Packet::init(1, Slice::from_fn_indexed(3, |i| i as u8)));
+        assert_eq!(packet.1, [0, 1, 2]);
+
+        let data: Arc<[String]> = Arc::emplace(Slice::from_fn_uninit(3, |i, slot: &mut std::mem::MaybeUninit<String>| {
+            slot.write(i.to_string())
+        }));
+        assert_eq!(*data, ["0", "1", "2"]);
+        let empty: Box<[u8]> = Box::emplace(Slice::from_fn_uninit(0, |_, _: &mut std::mem::MaybeUninit<u8>| unreachable!()));
+        assert!(empty.is_empty());
+
+        let dropped = &Cell::new(0);
+        let init = SliceIndexedFnInitializer::new(4, |i| {
+            assert!(i < 2, "third");
+            DropCounter(dropped)
+        });
+        assert!(catch_panic(|| Box::emplace(init)).is_none());
+        assert_eq!(dropped.get(), 2);
+        let mut other = DropCounter(dropped);
+        let other = &mut other as *mut DropCounter;
+        let init = SliceUninitFnInitializer::new(3, |i, slot| {
+            if i < 2 {
+                slot.write(DropCounter(dropped))
+            } else {
+                unsafe { &mut *other }
+            }
+        });
+        assert!(catch_panic(|| Box::emplace(init)).is_none());
+        assert_eq!(dropped.get(), 4);
+    }
+
     #[test]
     fn test_slice_iter_initializer() {
         let init = SliceIterInitializer::new(100, 0..100);
@@ -525,11 +1900,775 @@ pub mod test {
                 *ptr = x as u8;
                 ptr = ptr.add(1);
             }
-            return NonNull::new(std::ptr::slice_from_raw_parts_mut(tmp, 10)).expect("error when creating NonNull");
+            NonNull::new(std::ptr::slice_from_raw_parts_mut(tmp, 10)).expect("error when creating NonNull")
         }});
         let data = alloc(init);
         for x in 0..10 {
             assert_eq!(data[x], x as u8)
         }
     }
+
+    struct DropCounter<'a>(&'a Cell<usize>);
+
+    impl Drop for DropCounter<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[repr(align(64))]
+    struct OverAligned(usize);
+
+    fn test1_init(len: usize) -> impl EmplaceInitializer<Output = Test1<u8, u16, u32, u64>> {
+        Test1Init {
+            a: 7usize,
+            t: TestInit {
+                a: 1u8,
+                b: 2u16,
+                c: 3u32,
+                dst: SliceIterInitializer::new(len, (0..).map(|i| (i as u32, i as u64))),
+            },
+        }
+    }
+
+    #[test]
+    fn test_rc_emplace() {
+        let mut a: Rc<Test1<u8, u16, u32, u64>> = Rc::emplace(test1_init(4));
+        assert_eq!(a.a, 7);
+        assert_eq!((a.t.a, a.t.b, a.t.c), (1, 2, 3));
+        assert_eq!(a.t.dst, [(0, 0), (1, 1), (2, 2), (3, 3)]);
+
+        let b = a.clone();
+        assert_eq!(Rc::strong_count(&a), 2);
+        assert!(Rc::get_mut(&mut a).is_none());
+        drop(b);
+        Rc::get_mut(&mut a).unwrap().t.dst[3].1 = 42;
+
+        let weak = Rc::downgrade(&a);
+        assert_eq!(Rc::weak_count(&a), 1);
+        assert!(Rc::get_mut(&mut a).is_none());
+        assert_eq!(weak.upgrade().unwrap().t.dst[3], (3, 42));
+        drop(a);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_arc_emplace() {
+        let mut a: Arc<Test1<u8, u16, u32, u64>> = Arc::emplace(test1_init(3));
+        assert_eq!(a.a, 7);
+        assert_eq!(a.t.dst, [(0, 0), (1, 1), (2, 2)]);
+
+        let b = a.clone();
+        assert_eq!(Arc::strong_count(&a), 2);
+        assert!(Arc::get_mut(&mut a).is_none());
+        drop(b);
+        Arc::get_mut(&mut a).unwrap().t.c = 9;
+
+        let weak = Arc::downgrade(&a);
+        assert_eq!(weak.upgrade().unwrap().t.c, 9);
+        drop(a);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_rc_arc_drop() {
+        let dropped = Cell::new(0);
+        let a: Rc<[DropCounter]> = Rc::emplace(SliceFnInitializer::new(5, || DropCounter(&dropped)));
+        let b = a.clone();
+        drop(a);
+        assert_eq!(dropped.get(), 0);
+        drop(b);
+        assert_eq!(dropped.get(), 5);
+
+        let dropped = Cell::new(0);
+        let a: Arc<[DropCounter]> = Arc::emplace(SliceFnInitializer::new(5, || DropCounter(&dropped)));
+        let weak = Arc::downgrade(&a);
+        drop(a);
+        assert_eq!(dropped.get(), 5);
+        drop(weak);
+    }
+
+    #[test]
+    fn test_rc_arc_over_aligned() {
+        let a: Rc<[OverAligned]> = Rc::emplace(SliceIterInitializer::new(3, (0..).map(OverAligned)));
+        assert_eq!(a.as_ptr() as usize % 64, 0);
+        assert_eq!(a[2].0, 2);
+
+        let a: Arc<dyn Debug> = Arc::emplace(CoercionInitializer::new(OverAlignedDebug(OverAligned(5))));
+        assert_eq!(Arc::as_ptr(&a) as *const u8 as usize % 64, 0);
+        assert_eq!(format!("{:?}", a.clone()), "5");
+    }
+
+    struct OverAlignedDebug(OverAligned);
+
+    impl Debug for OverAlignedDebug {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.0 .0)
+        }
+    }
+
+    #[dst]
+    struct Record<'a> {
+        id: DropCounter<'a>,
+        items: [DropCounter<'a>],
+    }
+
+    #[dst]
+    struct Records<'a> {
+        count: usize,
+        record: Record<'a>,
+    }
+
+    #[test]
+    fn test_try_emplace() {
+        let init = SliceTryIterInitializer::new(4, (0..4).map(Ok::<usize, ()>));
+        let data: Box<[usize]> = Box::try_emplace(init).unwrap();
+        assert_eq!(*data, [0, 1, 2, 3]);
+
+        let init = SliceTryIterInitializer::new(4, (0..4).map(|i| if i < 2 { Ok(i) } else { Err(i) }));
+        assert!(matches!(Box::<[usize]>::try_emplace(init), Err(EmplaceError::Init(2))));
+
+        let mut i = 0;
+        let data: Rc<[usize]> = Rc::try_emplace(Slice::try_fn_init(3, || {
+            i += 1;
+            Ok::<_, ()>(i)
+        }))
+        .unwrap();
+        assert_eq!(*data, [1, 2, 3]);
+
+        let data: Arc<[usize]> = Arc::try_emplace(Slice::try_iter_init(2, [Ok::<_, ()>(5), Ok(6)].into_iter())).unwrap();
+        assert_eq!(*data, [5, 6]);
+    }
+
+    #[test]
+    fn test_try_emplace_drops_written() {
+        let dropped = Cell::new(0);
+        let mut i = 0;
+        let init = SliceTryFnInitializer::new(5, || {
+            i += 1;
+            if i == 4 {
+                Err("fourth")
+            } else {
+                Ok(DropCounter(&dropped))
+            }
+        });
+        assert!(matches!(Box::<[DropCounter]>::try_emplace(init), Err(EmplaceError::Init("fourth"))));
+        assert_eq!(dropped.get(), 3);
+    }
+
+    #[test]
+    fn test_try_emplace_dst() {
+        let dropped = &Cell::new(0);
+        let init = |fail_at: usize| RecordsInit {
+            count: 1,
+            record: RecordInit {
+                id: DropCounter(dropped),
+                items: Slice::try_iter_init(
+                    3,
+                    (0..3).map(move |i| if i == fail_at { Err(i) } else { Ok(DropCounter(dropped)) }),
+                ),
+            },
+        };
+
+        let records: Rc<Records> = Rc::try_emplace(init(usize::MAX)).unwrap();
+        assert_eq!(records.count, 1);
+        assert_eq!(records.record.items.len(), 3);
+        drop(records);
+        assert_eq!(dropped.get(), 4);
+
+        dropped.set(0);
+        assert!(matches!(Arc::<Records>::try_emplace(init(2)), Err(EmplaceError::Init(2))));
+        assert_eq!(dropped.get(), 3);
+    }
+
+    fn catch_panic<R>(f: impl FnOnce() -> R) -> Option<R> {
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).ok()
+    }
+
+    #[test]
+    fn test_slice_panic_safety() {
+        let dropped = &Cell::new(0);
+        let mut i = 0;
+        let init = SliceFnInitializer::new(5, || {
+            i += 1;
+            assert!(i < 4, "fourth");
+            DropCounter(dropped)
+        });
+        assert!(catch_panic(|| Box::emplace(init)).is_none());
+        assert_eq!(dropped.get(), 3);
+
+        dropped.set(0);
+        let init = SliceIterInitializer::new(5, (0..2).map(|_| DropCounter(dropped)));
+        assert!(catch_panic(|| Rc::emplace(init)).is_none());
+        assert_eq!(dropped.get(), 2);
+
+        dropped.set(0);
+        let init = SliceTryIterInitializer::new(5, (0..5).map(|i| {
+            assert!(i < 1, "second");
+            Ok::<_, ()>(DropCounter(dropped))
+        }));
+        assert!(catch_panic(|| Arc::try_emplace(init)).is_none());
+        assert_eq!(dropped.get(), 1);
+    }
+
+    struct CloneCounter<'a>(&'a Cell<usize>, bool);
+
+    impl Clone for CloneCounter<'_> {
+        fn clone(&self) -> Self {
+            assert!(self.1, "not cloneable");
+            CloneCounter(self.0, self.1)
+        }
+    }
+
+    impl Drop for CloneCounter<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn test_slice_copy_clone() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        let copied: Box<[u8]> = Box::emplace(Slice::copy_from(&bytes));
+        assert_eq!(*copied, *bytes);
+        let packet: Arc<Packet> = Arc::emplace(Packet::init(1, Slice::copy_from(&bytes[..3])));
+        assert_eq!((packet.0, &packet.1), (1, &[0, 1, 2][..]));
+        let empty: Rc<[(u64, u8)]> = Rc::emplace(SliceCopyInitializer::new(&[]));
+        assert!(empty.is_empty());
+        let units: Box<[()]> = Box::emplace(Slice::copy_from(&[(); 3]));
+        assert_eq!(units.len(), 3);
+
+        let strings = [String::from("a"), String::from("b")];
+        let cloned: Rc<[String]> = Rc::emplace(Slice::clone_from(&strings));
+        assert_eq!(*cloned, strings);
+        let packet = Packet::boxed_from_slice(2, &bytes[..2]);
+        assert_eq!((packet.0, &packet.1), (2, &[0, 1][..]));
+
+        let dropped = &Cell::new(0);
+        let src = [CloneCounter(dropped, true), CloneCounter(dropped, true), CloneCounter(dropped, false)];
+        assert!(catch_panic(|| Box::emplace(SliceCloneInitializer::new(&src))).is_none());
+        assert_eq!(dropped.get(), 2);
+        let cloned = Box::emplace(SliceCloneInitializer::new(&src[..2]));
+        drop((cloned, src));
+        assert_eq!(dropped.get(), 7);
+    }
+
+    #[test]
+    fn test_slice_from_vec() {
+        let strings: Box<[String]> = Box::emplace(Slice::from_vec(vec![String::from("a"), String::from("b")]));
+        assert_eq!(*strings, ["a", "b"]);
+        let packet: Rc<Packet> = Rc::emplace(Packet::init(1, Slice::from_boxed_slice(Box::new([2, 3]))));
+        assert_eq!((packet.0, &packet.1), (1, &[2, 3][..]));
+        let mut vec = Vec::with_capacity(10);
+        vec.push(1u64);
+        let slice: Arc<[u64]> = Arc::emplace(SliceVecInitializer::new(vec));
+        assert_eq!(*slice, [1]);
+        let empty: Box<[u8]> = Box::emplace(SliceVecInitializer::from(Vec::new()));
+        assert!(empty.is_empty());
+        let units: Box<[()]> = Box::emplace(Slice::from_vec(vec![(); 4]));
+        assert_eq!(units.len(), 4);
+
+        let dropped = &Cell::new(0);
+        let counters: Box<[DropCounter]> = Box::emplace(Slice::from_vec((0..3).map(|_| DropCounter(dropped)).collect()));
+        assert_eq!(dropped.get(), 0);
+        drop(counters);
+        assert_eq!(dropped.get(), 3);
+
+        let init = SliceVecInitializer::new(vec![1u64; 4]);
+        match crate::stack::StackDst::<[u64], 8>::try_emplace(init) {
+            Err(EmplaceError::Alloc(_, init)) => assert_eq!(init.fallback(), [1; 4]),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_slice_exact_iter() {
+        let slice: Box<[u32]> = Box::emplace(Slice::from_exact_iter([1, 2, 3]));
+        assert_eq!(*slice, [1, 2, 3]);
+        let packet: Rc<Packet> = Rc::emplace(Packet::init(1, Slice::from_exact_iter(vec![4, 5])));
+        assert_eq!((packet.0, &packet.1), (1, &[4, 5][..]));
+        let chained: Arc<[u64]> = Arc::emplace(Slice::from_trusted_len((0..2).chain(5..7)));
+        assert_eq!(*chained, [0, 1, 5, 6]);
+        let empty: Box<[String]> = Box::emplace(Slice::from_trusted_len(std::iter::empty()));
+        assert!(empty.is_empty());
+
+        let dropped = &Cell::new(0);
+        let counters: Box<[DropCounter]> = Box::emplace(Slice::from_trusted_len((0..3).map(|_| DropCounter(dropped))));
+        drop(counters);
+        assert_eq!(dropped.get(), 3);
+        let iter = (0..3).map(|i| {
+            assert!(i < 2, "third");
+            DropCounter(dropped)
+        });
+        assert!(catch_panic(|| Box::emplace(SliceTrustedLenInitializer::new(iter))).is_none());
+        assert_eq!(dropped.get(), 5);
+    }
+
+    #[test]
+    fn test_slice_checked_iter() {
+        let slice: Box<[u8]> = Box::try_emplace(Slice::checked_iter_init(3, 0..3)).unwrap();
+        assert_eq!(*slice, [0, 1, 2]);
+        let init = SliceCheckedIterInitializer::new(3, 0u8..2);
+        let short = IterLengthError::Short { expected: 3, found: 2 };
+        assert!(matches!(Rc::try_emplace(init), Err(EmplaceError::Init(e)) if e == short));
+        assert_eq!(short.to_string(), "iterator has 2 items, expected 3");
+        let init = SliceCheckedIterInitializer::new(2, 0u8..);
+        assert!(matches!(Arc::try_emplace(init), Err(EmplaceError::Init(IterLengthError::Long { expected: 2 }))));
+        let packet: Box<Packet> = Box::try_emplace(Packet::init(1, Slice::checked_iter_init(0, std::iter::empty()))).unwrap();
+        assert!(packet.1.is_empty());
+        let init = SliceCheckedIterInitializer::new(2, 0u8..);
+        assert!(catch_panic(|| Box::emplace(init)).is_none());
+
+        let dropped = &Cell::new(0);
+        let init = SliceCheckedIterInitializer::new(2, (0..3).map(|_| DropCounter(dropped)));
+        assert!(Box::try_emplace(init).is_err());
+        assert_eq!(dropped.get(), 3);
+        let init = SliceCheckedIterInitializer::new(4, (0..3).map(|_| DropCounter(dropped)));
+        assert!(Box::try_emplace(init).is_err());
+        assert_eq!(dropped.get(), 6);
+    }
+
+    #[dst]
+    struct Scratch {
+        len: u32,
+        scratch: [u64],
+    }
+
+    #[test]
+    fn test_slice_repeat_zeroed() {
+        let strings: Box<[String]> = Box::emplace(Slice::<String>::repeat_init(3, String::from("a")));
+        assert_eq!(*strings, ["a", "a", "a"]);
+        let packet: Rc<Packet> = Rc::emplace(Packet::init(1, SliceRepeatInitializer::new(2, 7)));
+        assert_eq!(packet.1, [7, 7]);
+        let count = Rc::new(());
+        let empty: Box<[Rc<()>]> = Box::emplace(SliceRepeatInitializer::new(0, count.clone()));
+        assert!(empty.is_empty());
+        let repeated: Box<[Rc<()>]> = Box::emplace(SliceRepeatInitializer::new(2, count.clone()));
+        assert_eq!(Rc::strong_count(&count), 3);
+        drop(repeated);
+        assert_eq!(Rc::strong_count(&count), 1);
+
+        let zeroed: Box<[u64]> = Box::emplace(Slice::zeroed(1000));
+        assert!(zeroed.iter().all(|x| *x == 0));
+        let zeroed: Arc<[[char; 3]]> = Arc::emplace(SliceZeroedInitializer::new(2));
+        assert_eq!(*zeroed, [['\0'; 3]; 2]);
+        let scratch: Box<Scratch> = Box::try_emplace(Scratch::init(4, SliceZeroedInitializer::new(4))).unwrap();
+        assert_eq!((scratch.len, &scratch.scratch), (4, &[0; 4][..]));
+        let scratch: Rc<Scratch> = Rc::emplace(Scratch::init(2, Slice::zeroed(2)));
+        assert_eq!((scratch.len, &scratch.scratch), (2, &[0; 2][..]));
+        let mut init = Slice::zeroed(3);
+        let layout = init.layout().unwrap();
+        let mem = unsafe { alloc::alloc(layout) };
+        unsafe { mem.write_bytes(0xff, layout.size()) };
+        let written: *mut [bool] = init.emplace(NonNull::new(mem).unwrap()).as_ptr();
+        unsafe {
+            assert_eq!(*written, [false; 3]);
+            alloc::dealloc(mem, layout);
+        }
+    }
+
+    #[test]
+    fn test_dst_panic_safety() {
+        let dropped = &Cell::new(0);
+        let init = RecordsInit {
+            count: 1,
+            record: RecordInit {
+                id: DropCounter(dropped),
+                items: SliceIterInitializer::new(4, (0..4).map(|i| {
+                    assert!(i < 2, "third");
+                    DropCounter(dropped)
+                })),
+            },
+        };
+        assert!(catch_panic(|| Box::emplace(init)).is_none());
+        assert_eq!(dropped.get(), 3);
+
+        dropped.set(0);
+        let init = RecordsInit {
+            count: 1,
+            record: RecordInit {
+                id: DropCounter(dropped),
+                items: SliceTryFnInitializer::new(4, || -> Result<DropCounter, ()> { panic!("first") }),
+            },
+        };
+        assert!(catch_panic(|| Arc::try_emplace(init)).is_none());
+        assert_eq!(dropped.get(), 1);
+    }
+
+    #[test]
+    fn test_layout_overflow() {
+        let mut init = SliceIterInitializer::new(usize::MAX / 2, 0u16..);
+        assert!(init.layout().is_err());
+        assert!(matches!(Box::try_emplace(init), Err(EmplaceError::Layout(..))));
+
+        let init = SliceFnInitializer::new(isize::MAX as usize, || 0u8);
+        assert!(matches!(Rc::try_emplace(init), Err(EmplaceError::Layout(..))));
+
+        let mut init = RecordsInit {
+            count: 1,
+            record: RecordInit {
+                id: DropCounter(&Cell::new(0)),
+                items: SliceTryIterInitializer::new(usize::MAX / 4, std::iter::empty::<Result<_, ()>>()),
+            },
+        };
+        assert!(init.layout().is_err());
+        assert!(matches!(Arc::try_emplace(init), Err(EmplaceError::Layout(..))));
+
+        let init = SliceFnInitializer::new(usize::MAX, || 0u64);
+        assert!(catch_panic(|| Box::emplace(init)).is_none());
+    }
+
+    #[dst]
+    #[derive(Debug)]
+    struct Empty {
+        a: (),
+        tail: [()],
+    }
+
+    #[test]
+    fn test_zero_sized() {
+        let data: Box<[()]> = Box::emplace(SliceIterInitializer::new(0, std::iter::empty()));
+        assert_eq!(data.len(), 0);
+        let data: Box<[()]> = Box::emplace(SliceFnInitializer::new(5, || ()));
+        assert_eq!(data.len(), 5);
+        let data: Box<dyn Debug> = Box::emplace(CoercionInitializer::new(()));
+        assert_eq!(format!("{:?}", data), "()");
+        let data: Box<Empty> = Box::try_emplace(EmptyInit {
+            a: (),
+            tail: SliceFnInitializer::new(3, || ()),
+        })
+        .unwrap();
+        assert_eq!(data.tail.len(), 3);
+
+        let data: Rc<[()]> = Rc::emplace(SliceFnInitializer::new(2, || ()));
+        assert_eq!(Rc::clone(&data).len(), 2);
+        let data: Arc<dyn Debug> = Arc::emplace(CoercionInitializer::new(()));
+        assert_eq!(format!("{:?}", data), "()");
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_alloc_failure() {
+        let init = SliceFnInitializer::new(isize::MAX as usize / 2, || 0u8);
+        let Err(EmplaceError::Alloc(_, init)) = Box::try_emplace(init) else {
+            panic!("allocation should fail");
+        };
+        let Err(err) = Rc::try_emplace(init) else {
+            panic!("allocation should fail");
+        };
+        assert!(err.into_init().is_some());
+    }
+
+    #[dst]
+    #[derive(Debug)]
+    struct Packet(u32, [u8]);
+
+    #[dst]
+    #[derive(Debug)]
+    struct Pair<'a, T: Debug>(&'a str, T, [T]);
+
+    #[dst]
+    #[derive(Debug)]
+    struct Frame {
+        seq: u64,
+        packet: Packet,
+    }
+
+    #[dst]
+    #[derive(Debug)]
+    struct Tunnel(u16, Frame);
+
+    #[dst]
+    struct Datagram {
+        data_len: u16,
+        tail_len: u16,
+        data: [u8],
+    }
+
+    #[test]
+    fn test_tuple_struct() {
+        let mut packet: Box<Packet> = Box::emplace(PacketInit(7, Slice::iter_init(3, 1..)));
+        assert_eq!(packet.0, 7);
+        assert_eq!(packet.1, [1, 2, 3]);
+        packet.1[0] = 0;
+        assert_eq!(packet.1, [0, 2, 3]);
+        let fst = PacketFst(1, std::marker::PhantomData);
+        assert_eq!(fst.0, 1);
+
+        let pair: Rc<Pair<u16>> = Rc::emplace(PairInit("pair", 5u16, Slice::fn_init(2, || 6u16)));
+        assert_eq!((pair.0, pair.1, &pair.2), ("pair", 5, &[6, 6][..]));
+
+        let frame: Arc<Frame> = Arc::emplace(FrameInit {
+            seq: 9,
+            packet: PacketInit(1, Slice::iter_init(2, 0..)),
+        });
+        assert_eq!((frame.seq, frame.packet.0, &frame.packet.1), (9, 1, &[0, 1][..]));
+
+        let init = TunnelInit(3, FrameInit {
+            seq: 4,
+            packet: PacketInit(5, Slice::try_iter_init(2, [Ok::<_, ()>(6), Ok(7)].into_iter())),
+        });
+        let tunnel: Box<Tunnel> = Box::try_emplace(init).unwrap();
+        assert_eq!((tunnel.0, tunnel.1.seq, tunnel.1.packet.0), (3, 4, 5));
+        assert_eq!(tunnel.1.packet.1, [6, 7]);
+    }
+
+    #[dst]
+    #[repr(C, align(16))]
+    struct Padded {
+        a: u64,
+        b: u8,
+        tail: [u8],
+    }
+
+    #[test]
+    fn test_tail_offset() {
+        let data: Box<Padded> = Box::emplace(PaddedInit {
+            a: 1,
+            b: 2,
+            tail: Slice::iter_init(3, 3..),
+        });
+        assert_eq!(std::ptr::addr_of!(data.tail) as *const u8 as usize - &*data as *const Padded as *const u8 as usize, 9);
+        assert_eq!((data.a, data.b, &data.tail), (1, 2, &[3, 4, 5][..]));
+        assert_eq!(std::mem::size_of_val(&*data), 16);
+        assert_eq!(&*data as *const Padded as *const u8 as usize % 16, 0);
+    }
+
+    #[dst(init = "MessageBuilder", fst = "MessageHeader", init_vis = "pub(crate)", fst_vis = "pub(crate)")]
+    pub struct Message {
+        id: u32,
+        body: [u8],
+    }
+
+    #[dst(no_fst)]
+    #[derive(Debug)]
+    struct Blob<T: Debug>(T, [T]);
+
+    #[test]
+    fn test_dst_options() {
+        let init = MessageBuilder {
+            id: 1,
+            body: CoercionInitializer::<[u8; 2], [u8]>::new(*b"hi"),
+        };
+        let header = MessageHeader { id: 2, body: std::marker::PhantomData };
+        assert_eq!(header.id, 2);
+        let message: Box<Message> = Box::emplace(init);
+        assert_eq!((message.id, &message.body), (1, &b"hi"[..]));
+
+        let blob: Box<Blob<u16>> = Box::emplace(BlobInit(1, Slice::iter_init(2, 2..)));
+        assert_eq!(format!("{:?}", blob), "Blob(1, [2, 3])");
+    }
+
+    #[test]
+    fn test_constructors() {
+        let frame: Box<Frame> = Box::emplace(Frame::init(1, Packet::init(2, Slice::iter_init(2, 3..))));
+        assert_eq!((frame.seq, frame.packet.0, &frame.packet.1), (1, 2, &[3, 4][..]));
+
+        let packet = Packet::boxed_from_slice(1, &[1, 2, 3]);
+        assert_eq!((packet.0, &packet.1), (1, &[1, 2, 3][..]));
+        let packet = Packet::rc_from_iter(2, 2, [5, 6, 7]);
+        assert_eq!((packet.0, &packet.1), (2, &[5, 6][..]));
+        let pair = Pair::arc_from_slice("a", 1u8, &[2, 3]);
+        assert_eq!((pair.0, pair.1, &pair.2), ("a", 1, &[2, 3][..]));
+
+        let test = Test::boxed_from_iter(1usize, 2u8, 3u16, 2, (0..).map(|i| (3u16, i as u64)));
+        assert_eq!((test.a, test.b, test.c, &test.dst), (1, 2, 3, &[(3, 0), (3, 1)][..]));
+        let message = Message::rc_from_slice(3, b"hello");
+        assert_eq!((message.id, &message.body), (3, &b"hello"[..]));
+
+        let dropped = Cell::new(0);
+        let record = Record::boxed_from_iter(DropCounter(&dropped), 2, std::iter::repeat_with(|| DropCounter(&dropped)));
+        drop(record);
+        assert_eq!(dropped.get(), 3);
+        assert!(catch_panic(|| Packet::boxed_from_iter(1, 3, [1, 2])).is_none());
+
+        let datagram = Datagram::arc_from_iter(7, 8, 2, [1, 2, 3]);
+        assert_eq!((datagram.data_len, datagram.tail_len, &datagram.data), (7, 8, &[1, 2][..]));
+    }
+
+    #[test]
+    fn test_dst_clone() {
+        let frame: Box<Frame> = Box::emplace(Frame::init(1, Packet::init(2, Slice::iter_init(2, 3..))));
+        let cloned = frame.clone_box();
+        assert_eq!((cloned.seq, cloned.packet.0, &cloned.packet.1), (1, 2, &[3, 4][..]));
+        assert_ne!(&*frame as *const Frame, &*cloned as *const Frame);
+
+        let pair = Pair::boxed_from_slice("a", String::from("b"), &[String::from("c")]);
+        let rc = pair.clone_rc();
+        let arc = rc.clone_arc();
+        assert_eq!((arc.0, &arc.1, &arc.2), ("a", &String::from("b"), &[String::from("c")][..]));
+
+        let message = Message::boxed_from_slice(1, b"body");
+        assert_eq!(message.clone_arc().body, *b"body");
+        let blob = Blob::boxed_from_slice(1u8, &[2, 3]).clone_box();
+        assert_eq!(format!("{:?}", blob), "Blob(1, [2, 3])");
+
+        let slice: Box<[u16]> = Box::emplace(Slice::iter_init(3, 0..));
+        assert_eq!(slice.clone_box(), slice);
+        let s: &str = "dynamic";
+        assert_eq!(&*s.clone_box(), "dynamic");
+        assert_eq!(&*"".clone_rc(), "");
+    }
+
+    #[test]
+    fn test_into_parts() {
+        let packet = Packet::boxed_from_slice(1, &[2, 3]);
+        let (mut header, mut tail) = packet.into_parts();
+        assert_eq!((header.0, &tail[..]), (1, &[2, 3][..]));
+        header.0 = 4;
+        tail.push(5);
+        let packet = Packet::boxed_from_iter(header.0, tail.len(), tail);
+        assert_eq!((packet.0, &packet.1), (4, &[2, 3, 5][..]));
+
+        let frame: Box<Frame> = Box::emplace(Frame::init(1, Packet::init(2, Slice::iter_init(2, 3..))));
+        let (header, packet) = frame.into_parts();
+        assert_eq!((header.seq, packet.0, &packet.1), (1, 2, &[3, 4][..]));
+        let (header, tail) = packet.into_parts();
+        assert_eq!((header.0, tail), (2, vec![3, 4]));
+
+        let dropped = Cell::new(0);
+        let record = Record::boxed_from_iter(DropCounter(&dropped), 2, std::iter::repeat_with(|| DropCounter(&dropped)));
+        let (header, tail) = record.into_parts();
+        assert_eq!((dropped.get(), tail.len()), (0, 2));
+        drop((header, tail));
+        assert_eq!(dropped.get(), 3);
+
+        let empty: Box<Empty> = Box::emplace(Empty::init((), Slice::fn_init(2, || ())));
+        let (_, tail) = empty.into_parts();
+        assert_eq!(tail.len(), 2);
+
+        let labeled: Box<Labeled> = Box::emplace(Labeled::init(1, CoercionInitializer::new(OverAlignedDebug(OverAligned(2)))));
+        let (header, value) = labeled.into_parts();
+        assert_eq!((header.id, format!("{:?}", value)), (1, String::from("2")));
+        assert_eq!(&*value as *const dyn Debug as *const u8 as usize % 64, 0);
+    }
+
+    #[dst]
+    struct Labeled {
+        id: u8,
+        value: dyn Debug,
+    }
+
+    #[dst]
+    #[derive(Debug)]
+    struct Name {
+        id: u32,
+        s: str,
+    }
+
+    #[dst]
+    struct Obj<'a> {
+        tag: u8,
+        v: dyn Debug + 'a,
+    }
+
+    #[test]
+    fn test_str_dyn_tail() {
+        let name: Box<Name> = Box::emplace(Name::init(1, StrInitializer::new("name")));
+        assert_eq!((name.id, &name.s), (1, "name"));
+        assert_eq!(std::ptr::metadata(&*name), 4);
+        let name: Rc<Name> = Rc::emplace(NameInit { id: 2, s: StrInitializer::from(String::from("rc")) });
+        assert_eq!((name.id, &name.s), (2, "rc"));
+        let name: Arc<Name> = Arc::emplace(Name::init(3, StrInitializer::from(format_args!("{}-{}", "a", 1))));
+        assert_eq!(&name.s, "a-1");
+        let empty: Box<Name> = Box::emplace(Name::init(4, StrInitializer::from(format_args!(""))));
+        assert_eq!((empty.id, &empty.s), (4, ""));
+        let (header, s) = name.clone_box().into_parts();
+        assert_eq!((header.id, &*s), (3, "a-1"));
+        assert_eq!(format!("{:?}", empty.clone_rc()), r#"Name { id: 4, s: "" }"#);
+
+        let count = Rc::new(());
+        let obj: Box<Obj> = Box::emplace(Obj::init(1, CoercionInitializer::new([count.clone(), count.clone()])));
+        assert_eq!((obj.tag, format!("{:?}", &obj.v)), (1, String::from("[(), ()]")));
+        drop(obj);
+        assert_eq!(Rc::strong_count(&count), 1);
+        let obj: Rc<Obj> = Rc::emplace(ObjInit { tag: 2, v: CoercionInitializer::new(OverAlignedDebug(OverAligned(5))) });
+        assert_eq!((obj.tag, format!("{:?}", &obj.v)), (2, String::from("5")));
+        assert_eq!(&obj.v as *const dyn Debug as *const u8 as usize % 64, 0);
+        let obj: Arc<Obj> = Arc::emplace(Obj::init(3, CoercionInitializer::new("str")));
+        assert_eq!(format!("{:?}", &obj.v), r#""str""#);
+    }
+
+    #[test]
+    fn test_split() {
+        let mut frame: Box<Frame> = Box::emplace(Frame::init(1, Packet::init(2, Slice::iter_init(2, 3..))));
+        let (header, packet) = frame.split_mut();
+        *header.seq += packet.1.len() as u64;
+        let (packet_header, tail) = packet.split_mut();
+        *packet_header.0 = tail[0] as u32;
+        tail[1] = 0;
+        assert_eq!((frame.seq, frame.packet.0, &frame.packet.1), (3, 3, &[3, 0][..]));
+
+        let (header, packet) = frame.split();
+        assert_eq!((header.seq, packet.0), (3, 3));
+        assert_eq!(header as *const FrameFst as *const u8, &*frame as *const Frame as *const u8);
+
+        let mut test = Test::boxed_from_iter(1usize, 2u8, 3u16, 2, (0..).map(|i| (3u16, i as u64)));
+        let (header, tail) = test.split_mut();
+        (*header.a, *header.b, *header.c) = (4, 5, 6);
+        assert_eq!(tail, &[(3, 0), (3, 1)]);
+        assert_eq!((test.a, test.b, test.c, &test.dst), (4, 5, 6, &[(3, 0), (3, 1)][..]));
+
+        let mut padded: Box<Padded> = Box::emplace(Padded::init(1, 2, Slice::iter_init(3, 0..)));
+        let (header, tail) = padded.split();
+        assert_eq!((header.a, header.b, tail), (1, 2, &[0, 1, 2][..]));
+        let (header, tail) = padded.split_mut();
+        *header.b = 3;
+        tail.fill(4);
+        assert_eq!((padded.a, padded.b, &padded.tail), (1, 3, &[4, 4, 4][..]));
+    }
+
+    #[dst(init = "ChunkBuilder", init_vis = "pub(self)")]
+    struct Chunk {
+        tag: u8,
+        bytes: [u8],
+    }
+
+    #[test]
+    fn test_dst_init() {
+        let (x, y) = ((3u8, 4u16), (5, 6));
+        let t: Box<Test1<u8, u8, u8, u16>> = Box::emplace(dst_init!(Test1 {
+            a: 1,
+            t: Test { a: 1, b: 2, c: 3, dst: [x, y] }
+        }));
+        assert_eq!((t.a, t.t.a, t.t.b, t.t.c, &t.t.dst), (1, 1, 2, 3, &[x, y][..]));
+
+        let t: Box<Test1<u8, u8, u8, u16>> = Box::emplace(dst_init!(Test1 {
+            t: Test { dst: [x], c: 3, b: 2, a: 1 },
+            a: 1,
+        }));
+        assert_eq!((t.a, t.t.a, t.t.b, t.t.c, &t.t.dst), (1, 1, 2, 3, &[x][..]));
+
+        let len = 3;
+        let packet: Rc<Packet> = Rc::emplace(dst_init!(Packet { 0: 7, 1: vec![1; len] }));
+        assert_eq!((packet.0, &packet.1), (7, &[1, 1, 1][..]));
+        let tunnel: Arc<Tunnel> = Arc::emplace(dst_init!(self::Tunnel {
+            1: Frame { packet: Packet { 1: 0..2, 0: 3 }, seq: 2 },
+            0: 1,
+        }));
+        assert_eq!((tunnel.0, tunnel.1.seq, tunnel.1.packet.0, &tunnel.1.packet.1), (1, 2, 3, &[0, 1][..]));
+
+        let pair: Box<Pair<u32>> = Box::emplace(dst_init!(Pair { 0: "pair", 1: 1, 2: Slice::fn_init(2, || 9) }));
+        assert_eq!((pair.0, pair.1, &pair.2), ("pair", 1, &[9, 9][..]));
+        let chunk: Box<Chunk> = Box::emplace(dst_init!(Chunk { bytes: Some(5), tag: 1 }));
+        assert_eq!((chunk.tag, &chunk.bytes), (1, &[5][..]));
+        let init: dst_init::Init<Chunk, CoercionInitializer<[u8; 1], [u8]>> = ChunkBuilder { tag: 2, bytes: CoercionInitializer::new([6]) };
+        let chunk = Box::emplace(init);
+        assert_eq!((chunk.tag, &chunk.bytes), (2, &[6][..]));
+        let message: Box<Message> = Box::emplace(dst_init!(Message { body: *b"hi", id: 2 }));
+        assert_eq!((message.id, &message.body), (2, &b"hi"[..]));
+        let slice: Box<[String]> = Box::emplace(dst_init!(["a", "b"].map(String::from)));
+        assert_eq!(*slice, ["a", "b"]);
+        let empty: Box<[u8]> = Box::emplace(dst_init!([]));
+        assert!(empty.is_empty());
+
+        let dropped = &Cell::new(0);
+        let records: Box<Records> = Box::emplace(dst_init!(Records {
+            count: 1,
+            record: Record { id: DropCounter(dropped), items: (0..2).map(|_| DropCounter(dropped)) },
+        }));
+        assert_eq!((records.count, records.record.items.len()), (1, 2));
+        drop(records);
+        assert_eq!(dropped.get(), 3);
+    }
 }
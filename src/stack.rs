@@ -0,0 +1,327 @@
+//! Dynamic sized values stored inline, without heap allocation.
+use crate::{BoxExt, EmplaceError, EmplaceInitializer, Metadata, TryEmplaceInitializer};
+use std::alloc::{AllocError, Layout};
+use std::fmt::{self, Debug, Display, Formatter};
+use std::marker::PhantomData;
+use std::mem::{align_of, MaybeUninit};
+use std::ops::{Deref, DerefMut};
+use std::ptr::{self, NonNull};
+
+/// Default alignment of the inline buffer of `StackDst`, the alignment of `Align16`.
+pub const STACK_DST_ALIGN: usize = 16;
+
+/// The default alignment type of `StackDst` and `SmallDst`, aligning their buffer to
+/// `STACK_DST_ALIGN` bytes.
+#[repr(align(16))]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Align16;
+
+#[repr(C)]
+struct Buf<const N: usize, A> {
+    align: [A; 0],
+    bytes: [MaybeUninit<u8>; N],
+}
+
+impl<const N: usize, A> Buf<N, A> {
+    #[inline(always)]
+    fn new() -> Self {
+        Self { align: [], bytes: [MaybeUninit::uninit(); N] }
+    }
+}
+
+/// A dynamic sized value stored in an inline buffer of `N` bytes aligned to the alignment of `A`.
+///
+/// **The alignment of the buffer is fixed by `A`**, 16 bytes with the default `Align16`: values
+/// aligned to more never fit, whatever their size. Give a zero sized type of a larger alignment,
+/// like a `#[repr(align(64))]` struct, as `A` to store them.
+///
+/// `emplace` panics if the value does not fit in the buffer, `try_emplace` returns
+/// `EmplaceError::Alloc` instead. See `SmallDst` for a version spilling to the heap.
+///```rust
+/// #![feature(ptr_metadata)]
+/// use std::fmt::Debug;
+/// use dst_init::{BoxExt, CoercionInitializer};
+/// use dst_init::stack::StackDst;
+///
+/// let debug = StackDst::<dyn Debug, 64>::emplace(CoercionInitializer::new(1u8));
+/// assert_eq!(format!("{:?}", debug), "1");
+///
+/// #[repr(align(64))]
+/// struct Align64;
+/// #[repr(align(64))]
+/// #[derive(Debug)]
+/// struct Line([u8; 64]);
+///
+/// let line = StackDst::<dyn Debug, 64, Align64>::emplace(CoercionInitializer::new(Line([0; 64])));
+/// assert_eq!(&*line as *const dyn Debug as *const u8 as usize % 64, 0);
+///```
+pub struct StackDst<T: ?Sized, const N: usize, A = Align16> {
+    buf: Buf<N, A>,
+    meta: Metadata<T>,
+    phan: PhantomData<T>,
+}
+
+impl<T: ?Sized, const N: usize, A> StackDst<T, N, A> {
+    /// Whether a value of `layout` fits in the buffer.
+    #[inline(always)]
+    pub const fn fits(layout: Layout) -> bool {
+        layout.size() <= N && layout.align() <= align_of::<A>()
+    }
+
+    /// Emplace the value in a new buffer, its layout must fit in it.
+    #[inline(always)]
+    unsafe fn emplace_unchecked<Init: EmplaceInitializer<Output = T>>(init: Init) -> Self {
+        let mut buf = Buf::new();
+        let obj = init.emplace(NonNull::from(&mut buf).cast());
+        Self {
+            buf,
+            meta: ptr::metadata(obj.as_ptr()),
+            phan: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized, const N: usize, A> BoxExt for StackDst<T, N, A> {
+    type Output = T;
+
+    /// Emplace value in the inline buffer.
+    ///
+    /// # Panics
+    /// would panic if the value does not fit in the buffer.
+    fn emplace<Init: EmplaceInitializer<Output = Self::Output>>(mut init: Init) -> Self {
+        let layout = init.layout().unwrap();
+        assert!(
+            Self::fits(layout),
+            "value of {layout:?} does not fit in {N} bytes aligned to {}",
+            align_of::<A>()
+        );
+        unsafe { Self::emplace_unchecked(init) }
+    }
+
+    /// Try to emplace value in the inline buffer, returns `EmplaceError::Alloc` if the value
+    /// does not fit in it.
+    fn try_emplace<Init: TryEmplaceInitializer<Output = Self::Output>>(
+        mut init: Init,
+    ) -> Result<Self, EmplaceError<Init, Init::Error>> {
+        let layout = match init.layout() {
+            Ok(layout) => layout,
+            Err(e) => return Err(EmplaceError::Layout(e, init)),
+        };
+        if !Self::fits(layout) {
+            return Err(EmplaceError::Alloc(AllocError, init));
+        }
+        let mut buf = Buf::new();
+        let obj = init
+            .try_emplace(NonNull::from(&mut buf).cast())
+            .map_err(EmplaceError::Init)?;
+        Ok(Self {
+            meta: ptr::metadata(obj.as_ptr()),
+            buf,
+            phan: PhantomData,
+        })
+    }
+}
+
+impl<T: ?Sized, const N: usize, A> Deref for StackDst<T, N, A> {
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        unsafe { &*ptr::from_raw_parts(self.buf.bytes.as_ptr(), self.meta) }
+    }
+}
+
+impl<T: ?Sized, const N: usize, A> DerefMut for StackDst<T, N, A> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *ptr::from_raw_parts_mut(self.buf.bytes.as_mut_ptr(), self.meta) }
+    }
+}
+
+impl<T: ?Sized, const N: usize, A> Drop for StackDst<T, N, A> {
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(&mut **self) }
+    }
+}
+
+impl<T: ?Sized + Debug, const N: usize, A> Debug for StackDst<T, N, A> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + Display, const N: usize, A> Display for StackDst<T, N, A> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&**self, f)
+    }
+}
+
+/// A dynamic sized value stored inline like `StackDst` if it fits in `N` bytes aligned to the
+/// alignment of `A`, or in a `Box` otherwise. Values aligned to more than `A`, 16 bytes by
+/// default, always go to the heap.
+///```rust
+/// #![feature(ptr_metadata)]
+/// use dst_init::{BoxExt, Slice, SliceExt};
+/// use dst_init::stack::SmallDst;
+///
+/// let small = SmallDst::<[u32], 16>::emplace(Slice::iter_init(4, 0..));
+/// let large = SmallDst::<[u32], 16>::emplace(Slice::iter_init(5, 0..));
+/// assert!(small.is_inline() && !large.is_inline());
+/// assert_eq!(large[4], 4);
+///```
+pub enum SmallDst<T: ?Sized, const N: usize, A = Align16> {
+    Inline(StackDst<T, N, A>),
+    Heap(Box<T>),
+}
+
+impl<T: ?Sized, const N: usize, A> SmallDst<T, N, A> {
+    /// Whether the value is stored inline.
+    #[inline(always)]
+    pub fn is_inline(&self) -> bool {
+        matches!(self, SmallDst::Inline(_))
+    }
+}
+
+impl<T: ?Sized, const N: usize, A> BoxExt for SmallDst<T, N, A> {
+    type Output = T;
+
+    /// Emplace value in the inline buffer, or in a `Box` if it does not fit.
+    fn emplace<Init: EmplaceInitializer<Output = Self::Output>>(mut init: Init) -> Self {
+        match init.layout() {
+            Ok(layout) if StackDst::<T, N, A>::fits(layout) => unsafe {
+                SmallDst::Inline(StackDst::emplace_unchecked(init))
+            },
+            _ => SmallDst::Heap(Box::emplace(init)),
+        }
+    }
+
+    /// Try to emplace value in the inline buffer, or in a `Box` if it does not fit.
+    fn try_emplace<Init: TryEmplaceInitializer<Output = Self::Output>>(
+        mut init: Init,
+    ) -> Result<Self, EmplaceError<Init, Init::Error>> {
+        match init.layout() {
+            Ok(layout) if StackDst::<T, N, A>::fits(layout) => {
+                StackDst::try_emplace(init).map(SmallDst::Inline)
+            }
+            _ => Box::try_emplace(init).map(SmallDst::Heap),
+        }
+    }
+}
+
+impl<T: ?Sized, const N: usize, A> Deref for SmallDst<T, N, A> {
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        match self {
+            SmallDst::Inline(value) => value,
+            SmallDst::Heap(value) => value,
+        }
+    }
+}
+
+impl<T: ?Sized, const N: usize, A> DerefMut for SmallDst<T, N, A> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut T {
+        match self {
+            SmallDst::Inline(value) => value,
+            SmallDst::Heap(value) => value,
+        }
+    }
+}
+
+impl<T: ?Sized + Debug, const N: usize, A> Debug for SmallDst<T, N, A> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + Display, const N: usize, A> Display for SmallDst<T, N, A> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SmallDst, StackDst};
+    use crate::{self as dst_init, BoxExt, CoercionInitializer, EmplaceError, SliceFnInitializer};
+    use crate::{SliceIterInitializer, SliceTryIterInitializer};
+    use dst_init_macros::dst;
+    use std::alloc::AllocError;
+    use std::cell::Cell;
+    use std::fmt::Debug;
+
+    #[dst]
+    struct Header {
+        kind: u16,
+        bytes: [u8],
+    }
+
+    struct DropCounter<'a>(&'a Cell<usize>);
+
+    impl Drop for DropCounter<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[repr(align(32))]
+    struct OverAligned;
+
+    #[test]
+    fn test_stack_dst() {
+        let debug = StackDst::<dyn Debug, 64>::emplace(CoercionInitializer::new([1u64, 2]));
+        let moved = [debug];
+        assert_eq!(format!("{:?}", moved[0]), "[1, 2]");
+
+        let mut header = StackDst::<Header, 32>::emplace(HeaderInit {
+            kind: 1,
+            bytes: SliceIterInitializer::new(30, 0..),
+        });
+        header.bytes[0] = 9;
+        assert_eq!((header.kind, header.bytes.len(), header.bytes[0]), (1, 30, 9));
+
+        let init = SliceIterInitializer::new(31, 0..);
+        assert!(matches!(StackDst::<[u8], 30>::try_emplace(init), Err(EmplaceError::Alloc(AllocError, _))));
+        let init = CoercionInitializer::<_, dyn Send>::new(OverAligned);
+        assert!(matches!(StackDst::<dyn Send, 64>::try_emplace(init), Err(EmplaceError::Alloc(AllocError, _))));
+        let aligned = StackDst::<dyn Send, 64, OverAligned>::emplace(CoercionInitializer::new(OverAligned));
+        assert_eq!(&*aligned as *const dyn Send as *const u8 as usize % 32, 0);
+        let result = std::panic::catch_unwind(|| StackDst::<[u8], 4>::emplace(SliceFnInitializer::new(5, || 0)));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_stack_dst_drop() {
+        let dropped = &Cell::new(0);
+        let data = StackDst::<[DropCounter], 32>::emplace(SliceFnInitializer::new(3, || DropCounter(dropped)));
+        assert_eq!(data.len(), 3);
+        drop(data);
+        assert_eq!(dropped.get(), 3);
+
+        let init = SliceTryIterInitializer::new(3, (0..3).map(|i| if i < 2 { Ok(DropCounter(dropped)) } else { Err(i) }));
+        assert!(matches!(StackDst::<[DropCounter], 32>::try_emplace(init), Err(EmplaceError::Init(2))));
+        assert_eq!(dropped.get(), 5);
+    }
+
+    #[test]
+    fn test_small_dst() {
+        let dropped = &Cell::new(0);
+        let mut inline = SmallDst::<[DropCounter], 16>::emplace(SliceFnInitializer::new(2, || DropCounter(dropped)));
+        let heap = SmallDst::<[DropCounter], 16>::emplace(SliceFnInitializer::new(3, || DropCounter(dropped)));
+        assert!(inline.is_inline());
+        assert!(!heap.is_inline());
+        assert_eq!(inline.len() + heap.len(), 5);
+        inline[0] = DropCounter(dropped);
+        assert_eq!(dropped.get(), 1);
+        drop((inline, heap));
+        assert_eq!(dropped.get(), 6);
+
+        let aligned = SmallDst::<dyn Send, 64>::emplace(CoercionInitializer::new(OverAligned));
+        assert!(!aligned.is_inline());
+        assert_eq!(&*aligned as *const dyn Send as *const u8 as usize % 32, 0);
+        let aligned = SmallDst::<dyn Send, 64, OverAligned>::emplace(CoercionInitializer::new(OverAligned));
+        assert!(aligned.is_inline());
+    }
+}
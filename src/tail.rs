@@ -0,0 +1,229 @@
+//! Resizing the slice tail of boxed dynamic sized types in place.
+use std::alloc::{handle_alloc_error, Allocator, Global, Layout, LayoutError};
+use std::iter;
+use std::ptr::{self, NonNull, Pointee};
+
+/// Dynamic sized types whose tail is a slice `[Self::Elem]`, implemented by `#[dst]`.
+///
+/// # Safety
+/// The metadata of `Self` must be the length of the tail, and `tail_layout(len)` must return the
+/// layout of `Self` with `len` tail elements and the offset of the tail in it.
+pub unsafe trait SliceTail: Pointee<Metadata = usize> {
+    type Elem;
+
+    /// Layout of the value with `len` tail elements, and the offset of the tail.
+    fn tail_layout(len: usize) -> Result<(Layout, usize), LayoutError>;
+}
+
+/// Extension for `Box<T>` to resize the slice tail of `T` by reallocation.
+///```rust
+/// #![feature(ptr_metadata)]
+/// use dst_init::dst;
+/// use dst_init::tail::DstBoxExt;
+///
+/// #[dst]
+/// struct Packet {
+///     id: u32,
+///     data: [u8],
+/// }
+///
+/// let packet = Packet::boxed_from_slice(1, &[1, 2]);
+/// let packet = packet.extend_tail([3, 4]);
+/// assert_eq!(packet.data, [1, 2, 3, 4]);
+/// let packet = packet.resize_tail(6, 0).truncate_tail(5);
+/// assert_eq!((packet.id, &packet.data), (1, &[1, 2, 3, 4, 0][..]));
+///```
+pub trait DstBoxExt: Sized {
+    type Elem;
+
+    /// Resize the tail to `new_len` elements, filling the new ones with clones of `fill`.
+    ///
+    /// # Panics
+    /// would panic if the size of the value overflows.
+    fn resize_tail(self, new_len: usize, fill: Self::Elem) -> Self
+    where
+        Self::Elem: Clone;
+
+    /// Append the items of `iter` to the tail.
+    ///
+    /// # Panics
+    /// would panic if the size of the value overflows.
+    fn extend_tail<I: IntoIterator<Item = Self::Elem>>(self, iter: I) -> Self;
+
+    /// Drop the tail elements after the first `new_len` ones and shrink the allocation.
+    fn truncate_tail(self, new_len: usize) -> Self;
+}
+
+/// Layout of `T` with `len` tail elements, panics on overflow.
+#[inline(always)]
+fn tail_layout<T: SliceTail + ?Sized>(len: usize) -> (Layout, usize) {
+    T::tail_layout(len).expect("the size of the value overflows")
+}
+
+/// Reallocate the value from `len` to `new_len` tail elements, without touching the elements.
+#[inline(always)]
+unsafe fn realloc<T: SliceTail + ?Sized>(ptr: NonNull<u8>, len: usize, new_len: usize) -> NonNull<u8> {
+    let (old, _) = tail_layout::<T>(len);
+    let (new, _) = tail_layout::<T>(new_len);
+    let result = if new.size() >= old.size() {
+        Global.grow(ptr, old, new)
+    } else {
+        Global.shrink(ptr, old, new)
+    };
+    match result {
+        Ok(mem) => mem.cast(),
+        Err(_) => handle_alloc_error(new),
+    }
+}
+
+/// Owns a value being extended: `len` tail elements are initialized in an allocation for `cap`.
+/// Drops the value and frees the memory if extending unwinds.
+struct ExtendGuard<T: SliceTail + ?Sized> {
+    ptr: NonNull<u8>,
+    len: usize,
+    cap: usize,
+    phan: std::marker::PhantomData<Box<T>>,
+}
+
+impl<T: SliceTail + ?Sized> Drop for ExtendGuard<T> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::drop_in_place(ptr::from_raw_parts_mut::<T>(self.ptr.as_ptr(), self.len));
+            Global.deallocate(self.ptr, tail_layout::<T>(self.cap).0);
+        }
+    }
+}
+
+impl<T: SliceTail + ?Sized> DstBoxExt for Box<T> {
+    type Elem = T::Elem;
+
+    fn resize_tail(self, new_len: usize, fill: T::Elem) -> Self
+    where
+        T::Elem: Clone,
+    {
+        let len = ptr::metadata(&*self);
+        if new_len <= len {
+            self.truncate_tail(new_len)
+        } else {
+            self.extend_tail(iter::repeat_n(fill, new_len - len))
+        }
+    }
+
+    fn extend_tail<I: IntoIterator<Item = T::Elem>>(self, iter: I) -> Self {
+        let mut iter = iter.into_iter();
+        let len = ptr::metadata(&*self);
+        let ptr = unsafe { NonNull::new_unchecked(Box::into_raw(self).cast::<u8>()) };
+        let mut guard = ExtendGuard::<T> { ptr, len, cap: len, phan: Default::default() };
+        let offset = tail_layout::<T>(0).1;
+        while let Some(item) = iter.next() {
+            if guard.len == guard.cap {
+                let cap = guard.cap.saturating_mul(2).max(iter.size_hint().0.saturating_add(guard.cap + 1));
+                guard.ptr = unsafe { realloc::<T>(guard.ptr, guard.cap, cap) };
+                guard.cap = cap;
+            }
+            unsafe {
+                let tail = guard.ptr.as_ptr().add(offset).cast::<T::Elem>();
+                tail.add(guard.len).write(item);
+            }
+            guard.len += 1;
+        }
+        unsafe {
+            if guard.len != guard.cap {
+                guard.ptr = realloc::<T>(guard.ptr, guard.cap, guard.len);
+                guard.cap = guard.len;
+            }
+            let (ptr, len) = (guard.ptr, guard.len);
+            std::mem::forget(guard);
+            Box::from_raw(ptr::from_raw_parts_mut(ptr.as_ptr(), len))
+        }
+    }
+
+    fn truncate_tail(self, new_len: usize) -> Self {
+        let len = ptr::metadata(&*self);
+        if new_len >= len {
+            return self;
+        }
+        unsafe {
+            let ptr = NonNull::new_unchecked(Box::into_raw(self).cast::<u8>());
+            let offset = tail_layout::<T>(0).1;
+            let tail = ptr.as_ptr().add(offset).cast::<T::Elem>();
+            // like `Vec::truncate`, the value is leaked if dropping an element panics
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(tail.add(new_len), len - new_len));
+            let ptr = realloc::<T>(ptr, len, new_len);
+            Box::from_raw(ptr::from_raw_parts_mut(ptr.as_ptr(), new_len))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DstBoxExt;
+    use crate::{self as dst_init, BoxExt, Slice, SliceExt};
+    use dst_init_macros::dst;
+    use std::cell::Cell;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    #[dst]
+    struct Padded {
+        a: u64,
+        b: u8,
+        tail: [u16],
+    }
+
+    #[dst]
+    struct Empty {
+        a: (),
+        tail: [()],
+    }
+
+    #[derive(Clone)]
+    struct DropCounter<'a>(&'a Cell<usize>);
+
+    impl Drop for DropCounter<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[dst]
+    struct Counters<'a> {
+        head: DropCounter<'a>,
+        tail: [DropCounter<'a>],
+    }
+
+    #[test]
+    fn test_resize_tail() {
+        let padded: Box<Padded> = Box::emplace(Padded::init(1, 2, Slice::iter_init(1, 10..)));
+        let padded = padded.resize_tail(4, 7);
+        assert_eq!((padded.a, padded.b, &padded.tail), (1, 2, &[10, 7, 7, 7][..]));
+        let padded = padded.extend_tail((0..).take(100));
+        assert_eq!(padded.tail.len(), 104);
+        assert_eq!(padded.tail[103], 99);
+        let padded = padded.truncate_tail(2).truncate_tail(3);
+        assert_eq!(&padded.tail, &[10, 7]);
+        let padded = padded.resize_tail(0, 0).extend_tail(std::iter::empty());
+        assert_eq!((padded.a, padded.b, padded.tail.len()), (1, 2, 0));
+
+        let empty = Empty::boxed_from_iter((), 0, []).resize_tail(3, ());
+        assert_eq!(empty.tail.len(), 3);
+        assert_eq!(empty.truncate_tail(1).tail.len(), 1);
+    }
+
+    #[test]
+    fn test_resize_tail_drop() {
+        let dropped = &Cell::new(0);
+        let counters = Counters::boxed_from_slice(DropCounter(dropped), &[DropCounter(dropped)]);
+        assert_eq!(dropped.get(), 1);
+        let counters = counters.resize_tail(3, DropCounter(dropped));
+        assert_eq!((counters.tail.len(), dropped.get()), (3, 1));
+        let counters = counters.truncate_tail(1);
+        assert_eq!(dropped.get(), 3);
+
+        let iter = (0..5).map(|i| {
+            assert!(i < 2);
+            DropCounter(dropped)
+        });
+        assert!(catch_unwind(AssertUnwindSafe(|| counters.extend_tail(iter))).is_err());
+        assert_eq!(dropped.get(), 7);
+    }
+}
@@ -0,0 +1,392 @@
+//! Thin pointers to dynamic sized types. The pointer metadata (slice length or vtable) is stored
+//! in the allocation right before the value, so the handle is a single word wide.
+use crate::{
+    layout_with_header, AllocGuard, BoxExt, EmplaceError, EmplaceInitializer, Metadata,
+    TryEmplaceInitializer,
+};
+use std::alloc::{handle_alloc_error, Allocator, Global, Layout};
+use std::fmt::{self, Debug, Display, Formatter};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::process::abort;
+use std::ptr::NonNull;
+use std::sync::atomic::{fence, AtomicUsize, Ordering};
+use std::{mem, ptr};
+
+/// Allocate memory for `Header` followed by the value of `init`, emplace the value and write
+/// the header made from its metadata right before it. Returns the pointer to the value.
+#[inline(always)]
+unsafe fn emplace_thin<Header, Init: EmplaceInitializer>(
+    mut init: Init,
+    header: impl FnOnce(Metadata<Init::Output>) -> Header,
+) -> NonNull<u8> {
+    let (layout, offset) = layout_with_header::<Header, _>(&mut init).unwrap();
+    let mem = match Global.allocate(layout) {
+        Ok(mem) => mem.cast::<u8>(),
+        Err(_) => handle_alloc_error(layout),
+    };
+    let guard = AllocGuard { ptr: mem, layout, alloc: &Global };
+    let obj = init.emplace(mem.add(offset));
+    mem::forget(guard);
+    obj.cast::<Header>().sub(1).write(header(ptr::metadata(obj.as_ptr())));
+    obj.cast()
+}
+
+/// Same as `emplace_thin`, but with a fallible initializer.
+#[inline(always)]
+unsafe fn try_emplace_thin<Header, Init: TryEmplaceInitializer>(
+    mut init: Init,
+    header: impl FnOnce(Metadata<Init::Output>) -> Header,
+) -> Result<NonNull<u8>, EmplaceError<Init, Init::Error>> {
+    let (layout, offset) = match layout_with_header::<Header, _>(&mut init) {
+        Ok(layout) => layout,
+        Err(e) => return Err(EmplaceError::Layout(e, init)),
+    };
+    let mem = match Global.allocate(layout) {
+        Ok(mem) => mem.cast::<u8>(),
+        Err(e) => return Err(EmplaceError::Alloc(e, init)),
+    };
+    let guard = AllocGuard { ptr: mem, layout, alloc: &Global };
+    let obj = init.try_emplace(mem.add(offset)).map_err(EmplaceError::Init)?;
+    mem::forget(guard);
+    obj.cast::<Header>().sub(1).write(header(ptr::metadata(obj.as_ptr())));
+    Ok(obj.cast())
+}
+
+/// Drop the value and free the memory allocated by `emplace_thin`.
+#[inline(always)]
+unsafe fn drop_thin<Header, T: ?Sized>(value: *mut T) {
+    let (layout, offset) = Layout::new::<Header>()
+        .extend(Layout::for_value(&*value))
+        .unwrap();
+    ptr::drop_in_place(value);
+    let mem = NonNull::new_unchecked(value.cast::<u8>().sub(offset));
+    Global.deallocate(mem, layout.pad_to_align());
+}
+
+/// A `Box` for dynamic sized types as wide as a single pointer. The metadata of the value is
+/// stored in the allocation.
+///```rust
+/// #![feature(ptr_metadata)]
+/// use std::fmt::Debug;
+/// use dst_init::{BoxExt, CoercionInitializer, Slice, SliceExt};
+/// use dst_init::thin::ThinDstBox;
+///
+/// let slice: ThinDstBox<[usize]> = ThinDstBox::emplace(Slice::iter_init(3, 0..));
+/// assert_eq!(*slice, [0, 1, 2]);
+/// let debug: ThinDstBox<dyn Debug> = ThinDstBox::emplace(CoercionInitializer::new(1u8));
+/// assert_eq!(format!("{:?}", debug), "1");
+/// assert_eq!(size_of::<ThinDstBox<dyn Debug>>(), size_of::<usize>());
+///```
+pub struct ThinDstBox<T: ?Sized> {
+    ptr: NonNull<u8>,
+    phan: PhantomData<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for ThinDstBox<T> {}
+unsafe impl<T: ?Sized + Sync> Sync for ThinDstBox<T> {}
+
+impl<T: ?Sized> ThinDstBox<T> {
+    #[inline(always)]
+    fn as_ptr(&self) -> *mut T {
+        unsafe {
+            let meta = self.ptr.cast::<Metadata<T>>().sub(1).read();
+            ptr::from_raw_parts_mut(self.ptr.as_ptr(), meta)
+        }
+    }
+}
+
+impl<T: ?Sized> BoxExt for ThinDstBox<T> {
+    type Output = T;
+
+    /// Allocate memory by `std::alloc::alloc()` for the metadata and the value, emplace value in
+    /// it. Then use `ThinDstBox` wrap it.
+    fn emplace<Init: EmplaceInitializer<Output = Self::Output>>(init: Init) -> Self {
+        unsafe {
+            Self {
+                ptr: emplace_thin(init, |meta| meta),
+                phan: PhantomData,
+            }
+        }
+    }
+
+    /// Allocate memory by `std::alloc::alloc()` for the metadata and the value, try to emplace
+    /// value in it. Then use `ThinDstBox` wrap it.
+    fn try_emplace<Init: TryEmplaceInitializer<Output = Self::Output>>(
+        init: Init,
+    ) -> Result<Self, EmplaceError<Init, Init::Error>> {
+        unsafe {
+            Ok(Self {
+                ptr: try_emplace_thin(init, |meta| meta)?,
+                phan: PhantomData,
+            })
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for ThinDstBox<T> {
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        unsafe { &*self.as_ptr() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for ThinDstBox<T> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.as_ptr() }
+    }
+}
+
+impl<T: ?Sized> Drop for ThinDstBox<T> {
+    fn drop(&mut self) {
+        unsafe { drop_thin::<Metadata<T>, T>(self.as_ptr()) }
+    }
+}
+
+impl<T: ?Sized + Debug> Debug for ThinDstBox<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + Display> Display for ThinDstBox<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&**self, f)
+    }
+}
+
+/// Header of `ThinDstArc`, stored right before the value.
+#[repr(C)]
+struct ArcHeader<Meta> {
+    count: AtomicUsize,
+    meta: Meta,
+}
+
+/// An `Arc` for dynamic sized types as wide as a single pointer. The reference count and the
+/// metadata of the value are stored in the allocation. Weak references are not supported.
+pub struct ThinDstArc<T: ?Sized> {
+    ptr: NonNull<u8>,
+    phan: PhantomData<T>,
+}
+
+unsafe impl<T: ?Sized + Send + Sync> Send for ThinDstArc<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for ThinDstArc<T> {}
+
+impl<T: ?Sized> ThinDstArc<T> {
+    #[inline(always)]
+    fn header(&self) -> &ArcHeader<Metadata<T>> {
+        unsafe { self.ptr.cast::<ArcHeader<Metadata<T>>>().sub(1).as_ref() }
+    }
+
+    #[inline(always)]
+    fn as_ptr(&self) -> *mut T {
+        ptr::from_raw_parts_mut(self.ptr.as_ptr(), self.header().meta)
+    }
+
+    /// Number of `ThinDstArc` pointing to this value.
+    #[inline(always)]
+    pub fn strong_count(this: &Self) -> usize {
+        this.header().count.load(Ordering::Acquire)
+    }
+
+    /// Mutable reference to the value, if there is no other `ThinDstArc` pointing to it.
+    #[inline(always)]
+    pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+        if Self::strong_count(this) == 1 {
+            unsafe { Some(&mut *this.as_ptr()) }
+        } else {
+            None
+        }
+    }
+
+    /// Whether the two `ThinDstArc` point to the same value.
+    #[inline(always)]
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        this.ptr == other.ptr
+    }
+}
+
+impl<T: ?Sized> BoxExt for ThinDstArc<T> {
+    type Output = T;
+
+    /// Allocate memory by `std::alloc::alloc()` for the reference count, the metadata and the
+    /// value, emplace value in it. Then use `ThinDstArc` wrap it.
+    fn emplace<Init: EmplaceInitializer<Output = Self::Output>>(init: Init) -> Self {
+        unsafe {
+            Self {
+                ptr: emplace_thin(init, |meta| ArcHeader {
+                    count: AtomicUsize::new(1),
+                    meta,
+                }),
+                phan: PhantomData,
+            }
+        }
+    }
+
+    /// Allocate memory by `std::alloc::alloc()` for the reference count, the metadata and the
+    /// value, try to emplace value in it. Then use `ThinDstArc` wrap it.
+    fn try_emplace<Init: TryEmplaceInitializer<Output = Self::Output>>(
+        init: Init,
+    ) -> Result<Self, EmplaceError<Init, Init::Error>> {
+        unsafe {
+            Ok(Self {
+                ptr: try_emplace_thin(init, |meta| ArcHeader {
+                    count: AtomicUsize::new(1),
+                    meta,
+                })?,
+                phan: PhantomData,
+            })
+        }
+    }
+}
+
+impl<T: ?Sized> Clone for ThinDstArc<T> {
+    fn clone(&self) -> Self {
+        // same as `Arc`: abort rather than overflow the count
+        if self.header().count.fetch_add(1, Ordering::Relaxed) > isize::MAX as usize {
+            abort();
+        }
+        Self {
+            ptr: self.ptr,
+            phan: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for ThinDstArc<T> {
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        unsafe { &*self.as_ptr() }
+    }
+}
+
+impl<T: ?Sized> Drop for ThinDstArc<T> {
+    fn drop(&mut self) {
+        if self.header().count.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+        fence(Ordering::Acquire);
+        unsafe { drop_thin::<ArcHeader<Metadata<T>>, T>(self.as_ptr()) }
+    }
+}
+
+impl<T: ?Sized + Debug> Debug for ThinDstArc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + Display> Display for ThinDstArc<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ThinDstArc, ThinDstBox};
+    use crate::{self as dst_init, BoxExt, CoercionInitializer, DirectInitializer, EmplaceError};
+    use crate::{SliceFnInitializer, SliceIterInitializer, SliceTryIterInitializer};
+    use dst_init_macros::dst;
+    use std::cell::Cell;
+    use std::fmt::Debug;
+    use std::mem::size_of;
+    use std::thread;
+
+    #[dst]
+    #[derive(Debug)]
+    struct Packet {
+        src: u32,
+        dst: u32,
+        options: [u16],
+    }
+
+    struct DropCounter<'a>(&'a Cell<usize>);
+
+    impl Drop for DropCounter<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[repr(align(64))]
+    struct OverAligned(u8);
+
+    impl Debug for OverAligned {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "OverAligned({})", self.0)
+        }
+    }
+
+    #[test]
+    fn test_thin_box() {
+        assert_eq!(size_of::<ThinDstBox<Packet>>(), size_of::<usize>());
+        assert_eq!(size_of::<Option<ThinDstBox<dyn Debug>>>(), size_of::<usize>());
+
+        let mut packet: ThinDstBox<Packet> = ThinDstBox::emplace(PacketInit {
+            src: 1,
+            dst: 2,
+            options: SliceIterInitializer::new(3, 10..),
+        });
+        assert_eq!((packet.src, packet.dst), (1, 2));
+        assert_eq!(packet.options, [10, 11, 12]);
+        packet.options[1] = 0;
+        assert_eq!(packet.options, [10, 0, 12]);
+
+        let debug: ThinDstBox<dyn Debug> = ThinDstBox::emplace(CoercionInitializer::new(OverAligned(3)));
+        assert_eq!(&*debug as *const dyn Debug as *const u8 as usize % 64, 0);
+        assert_eq!(format!("{:?}", debug), "OverAligned(3)");
+
+        let sized: ThinDstBox<u64> = ThinDstBox::emplace(DirectInitializer::new(5));
+        assert_eq!(*sized, 5);
+
+        let empty: ThinDstBox<[()]> = ThinDstBox::emplace(SliceFnInitializer::new(4, || ()));
+        assert_eq!(empty.len(), 4);
+    }
+
+    #[test]
+    fn test_thin_box_drop() {
+        let dropped = &Cell::new(0);
+        let data: ThinDstBox<[DropCounter]> = ThinDstBox::emplace(SliceFnInitializer::new(3, || DropCounter(dropped)));
+        assert_eq!(dropped.get(), 0);
+        drop(data);
+        assert_eq!(dropped.get(), 3);
+
+        let init = SliceTryIterInitializer::new(3, (0..3).map(|i| if i < 2 { Ok(DropCounter(dropped)) } else { Err(i) }));
+        assert!(matches!(ThinDstBox::try_emplace(init), Err(EmplaceError::Init(2))));
+        assert_eq!(dropped.get(), 5);
+    }
+
+    #[test]
+    fn test_thin_arc() {
+        assert_eq!(size_of::<ThinDstArc<[u8]>>(), size_of::<usize>());
+
+        let mut packet: ThinDstArc<Packet> = ThinDstArc::emplace(PacketInit {
+            src: 1,
+            dst: 2,
+            options: SliceIterInitializer::new(2, 0..),
+        });
+        ThinDstArc::get_mut(&mut packet).unwrap().options[0] = 7;
+        let other = packet.clone();
+        assert!(ThinDstArc::ptr_eq(&packet, &other));
+        assert_eq!(ThinDstArc::strong_count(&packet), 2);
+        assert!(ThinDstArc::get_mut(&mut packet).is_none());
+
+        let handle = thread::spawn(move || other.options.iter().sum::<u16>());
+        assert_eq!(handle.join().unwrap(), 8);
+        assert_eq!(ThinDstArc::strong_count(&packet), 1);
+
+        let dropped = &Cell::new(0);
+        let data: ThinDstArc<[DropCounter]> = ThinDstArc::emplace(SliceFnInitializer::new(2, || DropCounter(dropped)));
+        let clone = data.clone();
+        drop(data);
+        assert_eq!(dropped.get(), 0);
+        drop(clone);
+        assert_eq!(dropped.get(), 2);
+    }
+}
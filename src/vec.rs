@@ -0,0 +1,545 @@
+//! Contiguous storage for dynamic sized values.
+use crate::{EmplaceError, EmplaceInitializer, Metadata, TryEmplaceInitializer};
+use std::alloc::{handle_alloc_error, AllocError, Allocator, Global, Layout, LayoutError};
+use std::fmt::{self, Debug, Formatter};
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+use std::ops::{Index, IndexMut};
+use std::ptr::NonNull;
+use std::{ptr, slice};
+
+/// A growable buffer storing dynamic sized values back-to-back, each at an offset aligned to its
+/// own alignment. The offset and the metadata of each value are kept in a separate table.
+///
+/// Values are moved bitwise when the buffer grows, like the elements of a `Vec`.
+///```rust
+/// #![feature(ptr_metadata)]
+/// use std::fmt::Debug;
+/// use dst_init::{CoercionInitializer, Slice, SliceExt};
+/// use dst_init::vec::DstVec;
+///
+/// let mut slices = DstVec::<[u16]>::new();
+/// slices.push(Slice::iter_init(2, 0..));
+/// slices.push(Slice::iter_init(3, 10..));
+/// assert_eq!(slices[1], [10, 11, 12]);
+///
+/// let mut values = DstVec::<dyn Debug>::new();
+/// values.push(CoercionInitializer::new(1u8));
+/// values.push(CoercionInitializer::new("str"));
+/// assert_eq!(format!("{:?}", values), r#"[1, "str"]"#);
+///```
+pub struct DstVec<T: ?Sized> {
+    buf: NonNull<u8>,
+    cap: usize,
+    align: usize,
+    len: usize,
+    entries: Vec<(usize, Metadata<T>)>,
+    phan: PhantomData<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for DstVec<T> {}
+unsafe impl<T: ?Sized + Sync> Sync for DstVec<T> {}
+
+/// Failure of making room in a `DstVec`.
+#[derive(Debug)]
+enum ReserveError {
+    /// The size of the buffer overflows
+    Layout(LayoutError),
+    /// The allocation of the given layout failed
+    Alloc(Layout),
+}
+
+impl From<LayoutError> for ReserveError {
+    #[inline(always)]
+    fn from(e: LayoutError) -> Self {
+        ReserveError::Layout(e)
+    }
+}
+
+/// Unwrap the result of making room, panics if the size overflows and reports allocation
+/// failure by `handle_alloc_error()`.
+#[inline(always)]
+fn reserved<R>(result: Result<R, ReserveError>) -> R {
+    match result {
+        Ok(r) => r,
+        Err(ReserveError::Layout(e)) => panic!("{}", e),
+        Err(ReserveError::Alloc(layout)) => handle_alloc_error(layout),
+    }
+}
+
+#[inline(always)]
+fn dangling(align: usize) -> NonNull<u8> {
+    unsafe { NonNull::new_unchecked(ptr::without_provenance_mut(align)) }
+}
+
+impl<T: ?Sized> DstVec<T> {
+    /// Create an empty `DstVec` without allocation.
+    pub fn new() -> Self {
+        Self {
+            buf: dangling(1),
+            cap: 0,
+            align: 1,
+            len: 0,
+            entries: Vec::new(),
+            phan: PhantomData,
+        }
+    }
+
+    /// Create an empty `DstVec` with room for `bytes` bytes of values and `count` values.
+    pub fn with_capacity(bytes: usize, count: usize) -> Self {
+        let mut vec = Self::new();
+        vec.entries.reserve(count);
+        if bytes != 0 {
+            reserved(vec.grow(bytes, 1));
+        }
+        vec
+    }
+
+    /// Number of values.
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether there is no value.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Number of bytes of the buffer used by the values, including the padding between them.
+    #[inline(always)]
+    pub fn bytes_len(&self) -> usize {
+        self.len
+    }
+
+    /// Number of bytes the buffer can hold without reallocation.
+    #[inline(always)]
+    pub fn bytes_capacity(&self) -> usize {
+        self.cap
+    }
+
+    /// Offset of the next value and the end of it in the buffer.
+    #[inline(always)]
+    fn needed(&self, layout: Layout) -> Result<(usize, usize), LayoutError> {
+        let offset = Layout::from_size_align(self.len, layout.align())?
+            .pad_to_align()
+            .size();
+        let end = Layout::from_size_align(offset, 1)?
+            .extend(Layout::from_size_align(layout.size(), 1)?)?
+            .0
+            .size();
+        Ok((offset, end))
+    }
+
+    /// Make the buffer hold at least `end` bytes aligned to `align`.
+    fn grow(&mut self, end: usize, align: usize) -> Result<(), ReserveError> {
+        if end <= self.cap && align <= self.align {
+            return Ok(());
+        }
+        let align = align.max(self.align);
+        let cap = if end <= self.cap {
+            self.cap
+        } else {
+            end.max(self.cap.saturating_mul(2))
+        };
+        let layout = Layout::from_size_align(cap, align)
+            .or_else(|_| Layout::from_size_align(end, align))?;
+        let buf = match Global.allocate(layout) {
+            Ok(mem) => mem.cast::<u8>(),
+            Err(_) => return Err(ReserveError::Alloc(layout)),
+        };
+        unsafe {
+            ptr::copy_nonoverlapping(self.buf.as_ptr(), buf.as_ptr(), self.len);
+            if self.cap != 0 {
+                let old = Layout::from_size_align_unchecked(self.cap, self.align);
+                Global.deallocate(self.buf, old);
+            }
+        }
+        self.buf = buf;
+        self.cap = layout.size();
+        self.align = align;
+        Ok(())
+    }
+
+    /// Reserve room for the value of `init`, returns the offset and the end of it.
+    #[inline(always)]
+    fn reserve_for<Init: EmplaceInitializer>(
+        &mut self,
+        init: &mut Init,
+    ) -> Result<(usize, usize), ReserveError> {
+        let layout = init.layout()?;
+        let (offset, end) = self.needed(layout)?;
+        self.grow(end, layout.align())?;
+        if self.entries.try_reserve(1).is_err() {
+            let entries = Layout::array::<(usize, Metadata<T>)>(self.entries.len() + 1)?;
+            return Err(ReserveError::Alloc(entries));
+        }
+        Ok((offset, end))
+    }
+
+    /// Emplace a value at the end.
+    ///
+    /// # Panics
+    /// would panic if the size of the buffer overflows, use `try_push` to handle it.
+    pub fn push<Init: EmplaceInitializer<Output = T>>(&mut self, mut init: Init) {
+        let (offset, end) = reserved(self.reserve_for(&mut init));
+        unsafe {
+            let obj = init.emplace(self.buf.add(offset));
+            self.entries.push((offset, ptr::metadata(obj.as_ptr())));
+        }
+        self.len = end;
+    }
+
+    /// Try to emplace a value at the end. The size overflowing the buffer, the allocation failing
+    /// and the initializer failing are reported by `EmplaceError`, and the values are left
+    /// unchanged.
+    pub fn try_push<Init: TryEmplaceInitializer<Output = T>>(
+        &mut self,
+        mut init: Init,
+    ) -> Result<(), EmplaceError<Init, Init::Error>> {
+        let (offset, end) = match self.reserve_for(&mut init) {
+            Ok(reserved) => reserved,
+            Err(ReserveError::Layout(e)) => return Err(EmplaceError::Layout(e, init)),
+            Err(ReserveError::Alloc(_)) => return Err(EmplaceError::Alloc(AllocError, init)),
+        };
+        unsafe {
+            let obj = init
+                .try_emplace(self.buf.add(offset))
+                .map_err(EmplaceError::Init)?;
+            self.entries.push((offset, ptr::metadata(obj.as_ptr())));
+        }
+        self.len = end;
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn ptr_of(&self, entry: (usize, Metadata<T>)) -> *mut T {
+        entry_ptr(self.buf, &entry)
+    }
+
+    /// Remove the last value and move it into a `Box`.
+    pub fn pop(&mut self) -> Option<Box<T>> {
+        let entry = self.entries.pop()?;
+        let src = self.ptr_of(entry);
+        self.len = entry.0;
+        unsafe {
+            let layout = Layout::for_value(&*src);
+            let mem = if layout.size() == 0 {
+                dangling(layout.align())
+            } else {
+                match Global.allocate(layout) {
+                    Ok(mem) => mem.cast::<u8>(),
+                    Err(_) => handle_alloc_error(layout),
+                }
+            };
+            ptr::copy_nonoverlapping(src.cast::<u8>(), mem.as_ptr(), layout.size());
+            Some(Box::from_raw(ptr::from_raw_parts_mut(mem.as_ptr(), entry.1)))
+        }
+    }
+
+    /// Drop all values, keeping the allocated buffer.
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Drop the values after the first `len` ones.
+    pub fn truncate(&mut self, len: usize) {
+        while self.entries.len() > len {
+            let entry = self.entries.pop().unwrap();
+            self.len = entry.0;
+            unsafe { ptr::drop_in_place(self.ptr_of(entry)) }
+        }
+    }
+
+    /// Reference to the value at `index`.
+    #[inline(always)]
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let entry = *self.entries.get(index)?;
+        unsafe { Some(&*self.ptr_of(entry)) }
+    }
+
+    /// Mutable reference to the value at `index`.
+    #[inline(always)]
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let entry = *self.entries.get(index)?;
+        unsafe { Some(&mut *self.ptr_of(entry)) }
+    }
+
+    /// Reference to the last value.
+    #[inline(always)]
+    pub fn last(&self) -> Option<&T> {
+        self.get(self.len().wrapping_sub(1))
+    }
+
+    /// Iterate over the values.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            buf: self.buf,
+            entries: self.entries.iter(),
+            phan: PhantomData,
+        }
+    }
+
+    /// Iterate over the values mutably.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            buf: self.buf,
+            entries: self.entries.iter(),
+            phan: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Default for DstVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ?Sized> Drop for DstVec<T> {
+    fn drop(&mut self) {
+        self.clear();
+        if self.cap != 0 {
+            unsafe {
+                let layout = Layout::from_size_align_unchecked(self.cap, self.align);
+                Global.deallocate(self.buf, layout);
+            }
+        }
+    }
+}
+
+impl<T: ?Sized> Index<usize> for DstVec<T> {
+    type Output = T;
+
+    #[inline(always)]
+    fn index(&self, index: usize) -> &T {
+        let entry = self.entries[index];
+        unsafe { &*self.ptr_of(entry) }
+    }
+}
+
+impl<T: ?Sized> IndexMut<usize> for DstVec<T> {
+    #[inline(always)]
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        let entry = self.entries[index];
+        unsafe { &mut *self.ptr_of(entry) }
+    }
+}
+
+impl<T: ?Sized + Debug> Debug for DstVec<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, T: ?Sized> IntoIterator for &'a DstVec<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<'a, T: ?Sized> IntoIterator for &'a mut DstVec<T> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> IterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+/// Iterator over the values of a `DstVec`.
+pub struct Iter<'a, T: ?Sized> {
+    buf: NonNull<u8>,
+    entries: slice::Iter<'a, (usize, Metadata<T>)>,
+    phan: PhantomData<&'a T>,
+}
+
+/// Mutable iterator over the values of a `DstVec`.
+pub struct IterMut<'a, T: ?Sized> {
+    buf: NonNull<u8>,
+    entries: slice::Iter<'a, (usize, Metadata<T>)>,
+    phan: PhantomData<&'a mut T>,
+}
+
+unsafe impl<T: ?Sized + Sync> Send for Iter<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for Iter<'_, T> {}
+unsafe impl<T: ?Sized + Send> Send for IterMut<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for IterMut<'_, T> {}
+
+#[inline(always)]
+fn entry_ptr<T: ?Sized>(buf: NonNull<u8>, &(offset, meta): &(usize, Metadata<T>)) -> *mut T {
+    unsafe { ptr::from_raw_parts_mut(buf.as_ptr().add(offset), meta) }
+}
+
+impl<'a, T: ?Sized> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<&'a T> {
+        let entry = self.entries.next()?;
+        unsafe { Some(&*entry_ptr(self.buf, entry)) }
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<'a, T: ?Sized> DoubleEndedIterator for Iter<'a, T> {
+    #[inline(always)]
+    fn next_back(&mut self) -> Option<&'a T> {
+        let entry = self.entries.next_back()?;
+        unsafe { Some(&*entry_ptr(self.buf, entry)) }
+    }
+}
+
+impl<T: ?Sized> ExactSizeIterator for Iter<'_, T> {}
+impl<T: ?Sized> FusedIterator for Iter<'_, T> {}
+
+impl<'a, T: ?Sized> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<&'a mut T> {
+        let entry = self.entries.next()?;
+        unsafe { Some(&mut *entry_ptr(self.buf, entry)) }
+    }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<'a, T: ?Sized> DoubleEndedIterator for IterMut<'a, T> {
+    #[inline(always)]
+    fn next_back(&mut self) -> Option<&'a mut T> {
+        let entry = self.entries.next_back()?;
+        unsafe { Some(&mut *entry_ptr(self.buf, entry)) }
+    }
+}
+
+impl<T: ?Sized> ExactSizeIterator for IterMut<'_, T> {}
+impl<T: ?Sized> FusedIterator for IterMut<'_, T> {}
+
+#[cfg(test)]
+mod test {
+    use super::DstVec;
+    use crate::{self as dst_init, CoercionInitializer, EmplaceError, SliceFnInitializer};
+    use crate::{SliceIterInitializer, SliceTryIterInitializer};
+    use dst_init_macros::dst;
+    use std::cell::Cell;
+    use std::fmt::{Debug, Formatter};
+
+    #[dst]
+    #[derive(Debug)]
+    struct Record {
+        id: u64,
+        data: [u8],
+    }
+
+    struct DropCounter<'a>(&'a Cell<usize>);
+
+    impl Drop for DropCounter<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[repr(align(64))]
+    struct OverAligned(u8);
+
+    impl Debug for OverAligned {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    #[test]
+    fn test_dst_vec() {
+        let mut records = DstVec::<Record>::new();
+        for id in 0..100 {
+            records.push(RecordInit {
+                id,
+                data: SliceIterInitializer::new(id as usize % 5, 0..),
+            });
+        }
+        assert_eq!(records.len(), 100);
+        assert_eq!(records[7].id, 7);
+        assert_eq!(records[7].data, [0, 1]);
+        records[7].data[0] = 9;
+        assert_eq!(records.get(7).unwrap().data, [9, 1]);
+        assert!(records.get(100).is_none());
+        for (i, record) in records.iter().enumerate() {
+            assert_eq!(record.id, i as u64);
+            assert_eq!(record as *const Record as *const u8 as usize % 8, 0);
+        }
+        for record in &mut records {
+            record.id += 1;
+        }
+        assert_eq!(records.iter().next_back().unwrap().id, 100);
+
+        let last = records.pop().unwrap();
+        assert_eq!((last.id, &last.data), (100, &[0, 1, 2, 3][..]));
+        assert_eq!(records.len(), 99);
+        assert_eq!(records.last().unwrap().id, 99);
+    }
+
+    #[test]
+    fn test_dst_vec_dyn() {
+        let mut values = DstVec::<dyn Debug>::new();
+        values.push(CoercionInitializer::new(1u8));
+        values.push(CoercionInitializer::new(OverAligned(2)));
+        values.push(CoercionInitializer::new(()));
+        values.push(CoercionInitializer::new(3u16));
+        for value in &values {
+            let align = std::mem::align_of_val(value);
+            assert_eq!(value as *const dyn Debug as *const u8 as usize % align, 0);
+        }
+        assert_eq!(format!("{:?}", values), "[1, 2, (), 3]");
+        assert_eq!(format!("{:?}", values.pop().unwrap()), "3");
+        assert_eq!(format!("{:?}", values.pop().unwrap()), "()");
+    }
+
+    #[test]
+    fn test_dst_vec_drop() {
+        let dropped = &Cell::new(0);
+        let mut values = DstVec::<[DropCounter]>::with_capacity(16, 4);
+        for len in 0..4 {
+            values.push(SliceFnInitializer::new(len, || DropCounter(dropped)));
+        }
+        values.truncate(3);
+        assert_eq!(dropped.get(), 3);
+        drop(values.pop());
+        assert_eq!(dropped.get(), 5);
+
+        let init = SliceTryIterInitializer::new(3, (0..3).map(|i| if i < 2 { Ok(DropCounter(dropped)) } else { Err(i) }));
+        assert!(matches!(values.try_push(init), Err(EmplaceError::Init(2))));
+        assert_eq!(dropped.get(), 7);
+        assert_eq!(values.len(), 2);
+
+        values.clear();
+        assert_eq!(dropped.get(), 8);
+        assert!(values.is_empty());
+        values.push(SliceFnInitializer::new(2, || DropCounter(dropped)));
+        drop(values);
+        assert_eq!(dropped.get(), 10);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_dst_vec_alloc_failure() {
+        let mut values = DstVec::<[u8]>::new();
+        values.push(SliceFnInitializer::new(2, || 1u8));
+        let init = SliceFnInitializer::new(isize::MAX as usize / 2, || 0u8);
+        assert!(matches!(values.try_push(init), Err(EmplaceError::Alloc(..))));
+        let init = SliceFnInitializer::new(isize::MAX as usize, || 0u8);
+        assert!(matches!(values.try_push(init), Err(EmplaceError::Layout(..))));
+        assert_eq!((values.len(), &values[0]), (1, &[1, 1][..]));
+    }
+}
@@ -0,0 +1,22 @@
+#![feature(ptr_metadata)]
+use dst_init::dst;
+
+#[dst(unknown)]
+struct Unknown {
+    a: u8,
+    tail: [u8],
+}
+
+#[dst(init = 1, fst = "not an ident", init_vis = "pub(nowhere")]
+struct Invalid {
+    a: u8,
+    tail: [u8],
+}
+
+#[dst(no_fst, fst = "Header")]
+struct Conflict {
+    a: u8,
+    tail: [u8],
+}
+
+fn main() {}
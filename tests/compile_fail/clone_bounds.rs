@@ -0,0 +1,23 @@
+#![feature(ptr_metadata)]
+use dst_init::{dst, DstClone};
+
+struct NotClone;
+
+#[dst]
+struct Header {
+    a: NotClone,
+    tail: [u8],
+}
+
+#[dst]
+struct Tail {
+    a: u8,
+    tail: [NotClone],
+}
+
+fn main() {
+    let header = Header::boxed_from_iter(NotClone, 0, []);
+    let _ = header.clone_box();
+    let tail = Tail::boxed_from_iter(0, 0, []);
+    let _ = tail.clone_box();
+}
@@ -0,0 +1,16 @@
+#![feature(ptr_metadata)]
+use dst_init::{dst, dst_init, BoxExt};
+
+#[dst]
+struct Packet {
+    id: u32,
+    data: [u8],
+}
+
+fn main() {
+    let packet = Box::<Packet>::emplace(dst_init!(Packet { id: 1, data: [2] }));
+    let _ = Box::<Packet>::emplace(dst_init!(Packet { ..*packet }));
+    let _ = Box::<Packet>::emplace(dst_init!(Packet { id: 1, data: 2 }));
+    let _ = Box::<Packet>::emplace(dst_init!(Packet { id: 1 }));
+    let _ = Box::<Packet>::emplace(dst_init!(Packet { data: [2], size: 1 }));
+}
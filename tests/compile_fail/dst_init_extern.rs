@@ -0,0 +1,9 @@
+#![feature(ptr_metadata)]
+use dst_init::{dst_init, BoxExt};
+use dst_init_upstream::{Packet, PacketInit};
+
+fn main() {
+    let _ = Box::<Packet>::emplace(PacketInit { id: 1, data: dst_init!([2]) });
+    let _ = Box::<Packet>::emplace(dst_init!(Packet { id: 1, data: [2] }));
+    let _ = Box::<Packet>::emplace(dst_init!(dst_init_upstream::Packet { id: 1, data: [2] }));
+}
@@ -0,0 +1,9 @@
+use dst_init::dst;
+
+#[dst]
+enum Foo {
+    A,
+    B(u8),
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use dst_init::dst;
+
+#[dst]
+struct Unit;
+
+#[dst]
+struct Empty {}
+
+#[dst]
+struct EmptyTuple();
+
+fn main() {}
@@ -0,0 +1,16 @@
+use dst_init::dst;
+
+#[dst]
+#[repr(C, packed)]
+struct Packed {
+    a: u8,
+    tail: [u16],
+}
+
+#[dst]
+#[repr(transparent)]
+struct Transparent {
+    tail: [u16],
+}
+
+fn main() {}
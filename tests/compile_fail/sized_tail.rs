@@ -0,0 +1,12 @@
+use dst_init::dst;
+
+#[dst]
+struct Array {
+    a: u8,
+    tail: [u8; 4],
+}
+
+#[dst]
+struct Primitive(u8, usize);
+
+fn main() {}
@@ -0,0 +1,9 @@
+use dst_init::dst;
+
+#[dst]
+union Foo {
+    a: u8,
+    b: u16,
+}
+
+fn main() {}
@@ -0,0 +1,26 @@
+#![feature(ptr_metadata)]
+
+mod inner {
+    use dst_init::dst;
+
+    #[dst(init_vis = "pub(self)", no_fst)]
+    pub struct Foo {
+        pub a: u8,
+        pub tail: [u8],
+    }
+
+    #[dst(fst_vis = "pub")]
+    pub struct Bar {
+        id: u8,
+        pub tail: [u8],
+    }
+}
+
+use inner::FooInit;
+
+fn main() {
+    let _ = inner::FooFst { a: 1, tail: std::marker::PhantomData };
+    let bar = inner::Bar::boxed_from_slice(1, &[2]);
+    let (header, _) = bar.split();
+    let _ = header.id;
+}
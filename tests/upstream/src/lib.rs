@@ -0,0 +1,8 @@
+#![feature(ptr_metadata)]
+use dst_init::dst;
+
+#[dst]
+pub struct Packet {
+    pub id: u32,
+    pub data: [u8],
+}